@@ -0,0 +1,251 @@
+//! Values bound to the variables of a template, they're substituted for `{{ name }}`
+//! in attributes and text when the template is rendered.
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::markup::ErrorKind;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Str(String),
+    List(Vec<Value>),
+    Map(Bindings),
+}
+
+impl Value {
+    /// Empty strings, `false`, `0` and empty lists are considered false.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Str(str) => !matches!(str.trim(), "" | "false" | "0"),
+            Value::List(items) => !items.is_empty(),
+            Value::Map(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Bindings {
+    vars: HashMap<String, Value>,
+}
+
+impl Bindings {
+    pub fn with(mut self, name: &str, val: &str) -> Self {
+        self.set(name, val);
+        self
+    }
+
+    pub fn with_value(mut self, name: &str, val: Value) -> Self {
+        self.set_value(name, val);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, val: &str) {
+        self.set_value(name, Value::Str(val.to_owned()));
+    }
+
+    pub fn set_value(&mut self, name: &str, val: Value) {
+        self.vars.insert(name.to_owned(), val);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.vars.get(name)
+    }
+}
+
+/// The variables visible to an expression, loops add their current item on top of the outer scope.
+pub enum Scope<'a> {
+    Root(&'a Bindings),
+    Layer {
+        bindings: &'a Bindings,
+        parent: &'a Scope<'a>,
+    },
+    Local {
+        name: &'a str,
+        value: &'a Value,
+        parent: &'a Scope<'a>,
+    },
+}
+
+impl<'a> Scope<'a> {
+    /// Resolves a variable, fields of list items are accessed with a dotted path like `item.name`.
+    pub fn lookup(&self, path: &str) -> Result<&'a Value, ErrorKind> {
+        let unbound = || ErrorKind::UnboundVariable(path.to_owned());
+        let mut segments = path.split('.');
+        let mut value = segments
+            .next()
+            .and_then(|name| self.find(name))
+            .ok_or_else(unbound)?;
+        for segment in segments {
+            value = match value {
+                Value::Map(map) => map.get(segment).ok_or_else(unbound)?,
+                _ => return Err(unbound()),
+            };
+        }
+        Ok(value)
+    }
+
+    pub fn lookup_str(&self, path: &str) -> Result<&'a str, ErrorKind> {
+        match self.lookup(path)? {
+            Value::Str(str) => Ok(str),
+            _ => Err(ErrorKind::TypeMismatch(path.to_owned(), "string")),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&'a Value> {
+        match self {
+            Scope::Root(bindings) => bindings.get(name),
+            Scope::Layer { bindings, parent } => bindings.get(name).or_else(|| parent.find(name)),
+            Scope::Local {
+                name: local,
+                value,
+                parent,
+            } => {
+                if *local == name {
+                    Some(*value)
+                } else {
+                    parent.find(name)
+                }
+            }
+        }
+    }
+}
+
+/// Replaces every `{{ name }}` in the string with the value bound to the variable.
+pub fn interpolate<'a>(str: &'a str, scope: &Scope) -> Result<Cow<'a, str>, ErrorKind> {
+    if !str.contains("{{") {
+        return Ok(Cow::Borrowed(str));
+    }
+    let mut res = String::with_capacity(str.len());
+    let mut rest = str;
+    while let Some(start) = rest.find("{{") {
+        res.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+        let end = tail
+            .find("}}")
+            .ok_or_else(|| ErrorKind::Syntax(format!("unclosed interpolation in '{}'", str)))?;
+        res.push_str(scope.lookup_str(tail[..end].trim())?);
+        rest = &tail[end + 2..];
+    }
+    res.push_str(rest);
+    Ok(Cow::Owned(res))
+}
+
+/// Resolves an attribute passed to an included template, an attribute consisting
+/// of a single `{{ name }}` passes the value as is so that lists can be passed along.
+pub fn bind(str: &str, scope: &Scope) -> Result<Value, ErrorKind> {
+    let trimmed = str.trim();
+    let single = trimmed
+        .strip_prefix("{{")
+        .and_then(|str| str.strip_suffix("}}"))
+        .filter(|path| !path.contains("{{") && !path.contains("}}"));
+    match single {
+        Some(path) => Ok(scope.lookup(path.trim())?.clone()),
+        None => Ok(Value::Str(interpolate(str, scope)?.into_owned())),
+    }
+}
+
+/// Evaluates a condition, either a variable tested for truthiness and optionally negated with `!`,
+/// or a comparison of two operands with `==`, `!=`, `<`, `<=`, `>` or `>=`.
+/// Operands are variables, numbers or quoted strings, they're compared as numbers when possible.
+pub fn evaluate(expr: &str, scope: &Scope) -> Result<bool, ErrorKind> {
+    const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "<", ">"];
+
+    let comparison = OPERATORS
+        .iter()
+        .filter_map(|op| expr.find(op).map(|idx| (idx, *op)))
+        .min_by_key(|(idx, op)| (*idx, std::cmp::Reverse(op.len())));
+    let (idx, op) = match comparison {
+        Some(comparison) => comparison,
+        None => {
+            let expr = expr.trim();
+            return match expr.strip_prefix('!') {
+                Some(path) => Ok(!scope.lookup(path.trim())?.is_truthy()),
+                None => Ok(scope.lookup(expr)?.is_truthy()),
+            };
+        }
+    };
+
+    let lhs = operand(&expr[..idx], scope)?;
+    let rhs = operand(&expr[idx + op.len()..], scope)?;
+    let ordering = match (lhs.parse::<f32>(), rhs.parse::<f32>()) {
+        (Ok(lhs), Ok(rhs)) => lhs.partial_cmp(&rhs),
+        _ => Some(lhs.cmp(rhs)),
+    };
+    let res = match op {
+        "==" => ordering.is_some_and(|ord| ord.is_eq()),
+        "!=" => ordering.is_none_or(|ord| ord.is_ne()),
+        "<" => ordering.is_some_and(|ord| ord.is_lt()),
+        "<=" => ordering.is_some_and(|ord| ord.is_le()),
+        ">" => ordering.is_some_and(|ord| ord.is_gt()),
+        _ => ordering.is_some_and(|ord| ord.is_ge()),
+    };
+    Ok(res)
+}
+
+fn operand<'a>(str: &'a str, scope: &Scope<'a>) -> Result<&'a str, ErrorKind> {
+    let str = str.trim();
+    let quoted = str
+        .strip_prefix('\'')
+        .and_then(|str| str.strip_suffix('\''))
+        .or_else(|| str.strip_prefix('"').and_then(|str| str.strip_suffix('"')));
+    match quoted {
+        Some(literal) => Ok(literal),
+        None if str.parse::<f32>().is_ok() => Ok(str),
+        None if str.is_empty() => Err(ErrorKind::InvalidValue(str.to_owned())),
+        None => scope.lookup_str(str),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{evaluate, interpolate, Bindings, Scope, Value};
+    use crate::markup::ErrorKind;
+
+    #[test]
+    fn it_interpolates_variables() {
+        let bindings = Bindings::default().with("name", "V").with("hp", "80");
+        let scope = Scope::Root(&bindings);
+        assert!(matches!(interpolate("plain", &scope), Ok(Cow::Borrowed("plain"))));
+        assert_eq!(interpolate("{{name}}: {{ hp }}%", &scope).unwrap(), "V: 80%");
+        assert!(matches!(interpolate("{{ hp", &scope), Err(ErrorKind::Syntax(_))));
+        assert!(matches!(
+            interpolate("{{ ammo }}", &scope),
+            Err(ErrorKind::UnboundVariable(name)) if name == "ammo"
+        ));
+    }
+
+    #[test]
+    fn it_evaluates_conditions() -> Result<(), ErrorKind> {
+        let item = Bindings::default().with("count", "3");
+        let bindings = Bindings::default()
+            .with("name", "V")
+            .with("hidden", "false")
+            .with_value("items", Value::List(vec![]));
+        let root = Scope::Root(&bindings);
+        let value = Value::Map(item);
+        let scope = Scope::Local {
+            name: "item",
+            value: &value,
+            parent: &root,
+        };
+
+        assert!(evaluate("name", &scope)?);
+        assert!(evaluate("!hidden", &scope)?);
+        assert!(!evaluate("items", &scope)?);
+        assert!(evaluate("item.count >= 3", &scope)?);
+        assert!(evaluate("item.count < 10", &scope)?);
+        assert!(evaluate("name == 'V'", &scope)?);
+        assert!(evaluate("name != \"Johnny\"", &scope)?);
+        assert!(matches!(
+            evaluate("item.missing", &scope),
+            Err(ErrorKind::UnboundVariable(_))
+        ));
+        assert!(matches!(
+            evaluate("items == 1", &scope),
+            Err(ErrorKind::TypeMismatch(..))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Templates loaded by name are kept parsed between loads. With hot reload enabled
+//! the files they were loaded from are checked for changes and stale entries are loaded again.
+//! Changes are reported through [invalidate], which script polls while hot reload is enabled.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::markup::{Result, Template};
+use crate::source::{Files, Source};
+
+thread_local! {
+    static CACHE: RefCell<Cache<Files>> = RefCell::new(Cache::new(Files::default()));
+}
+
+pub fn load(name: &str) -> Result<Arc<Template>> {
+    CACHE.with(|cache| cache.borrow_mut().load(name))
+}
+
+pub fn set_hot_reload(enabled: bool) {
+    CACHE.with(|cache| cache.borrow_mut().hot_reload = enabled);
+}
+
+pub fn is_hot_reload_enabled() -> bool {
+    CACHE.with(|cache| cache.borrow().hot_reload)
+}
+
+/// Registers the directory of templates named with the namespace, replacing the previous one.
+pub fn add_root(namespace: &str, path: &str) -> Result<()> {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.source.add_root(namespace, path)?;
+        let prefix = format!("{}:", namespace);
+        cache.entries.retain(|name, _| !name.starts_with(&prefix));
+        Ok(())
+    })
+}
+
+/// Drops the templates whose files have changed and returns their names,
+/// together with the templates reloaded since the last call.
+pub fn invalidate() -> Vec<String> {
+    CACHE.with(|cache| cache.borrow_mut().invalidate())
+}
+
+pub struct Cache<S> {
+    source: S,
+    entries: HashMap<String, Entry>,
+    hot_reload: bool,
+    // stale entries loaded again by hot reload that haven't been reported yet
+    reloaded: Vec<String>,
+}
+
+struct Entry {
+    template: Arc<Template>,
+    // the modification time of every file the template was loaded from
+    versions: Vec<(String, Option<SystemTime>)>,
+}
+
+impl<S: Source> Cache<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            entries: HashMap::new(),
+            hot_reload: false,
+            reloaded: vec![],
+        }
+    }
+
+    /// Returns the cached template, it's loaded again first if hot reload is enabled
+    /// and any of its files has changed since, it's then reported by [Cache::invalidate].
+    pub fn load(&mut self, name: &str) -> Result<Arc<Template>> {
+        if let Some(entry) = self.entries.get(name) {
+            if !self.hot_reload || self.is_fresh(entry) {
+                return Ok(entry.template.clone());
+            }
+            if !self.reloaded.iter().any(|reloaded| reloaded == name) {
+                self.reloaded.push(name.to_owned());
+            }
+        }
+        let template = Arc::new(Template::load_from(&self.source, name)?);
+        let versions = template
+            .dependencies()
+            .into_iter()
+            .map(|dep| (dep.to_owned(), self.source.modified(dep)))
+            .collect();
+        let entry = Entry {
+            template: template.clone(),
+            versions,
+        };
+        self.entries.insert(name.to_owned(), entry);
+        Ok(template)
+    }
+
+    pub fn invalidate(&mut self) -> Vec<String> {
+        let mut stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| !self.is_fresh(entry))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &stale {
+            self.entries.remove(name);
+        }
+        stale.append(&mut self.reloaded);
+        stale.sort();
+        stale.dedup();
+        stale
+    }
+
+    fn is_fresh(&self, entry: &Entry) -> bool {
+        entry
+            .versions
+            .iter()
+            .all(|(name, version)| self.source.modified(name) == *version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    use super::Cache;
+    use crate::binding::Bindings;
+    use crate::markup::Result;
+    use crate::source::Source;
+    use crate::tree::ElemKind;
+
+    #[derive(Default)]
+    struct Versioned {
+        files: RefCell<HashMap<&'static str, (&'static str, u64)>>,
+    }
+
+    impl Versioned {
+        fn write(&self, name: &'static str, markup: &'static str) {
+            let mut files = self.files.borrow_mut();
+            let version = files.get(name).map_or(0, |(_, version)| version + 1);
+            files.insert(name, (markup, version));
+        }
+    }
+
+    impl Source for &Versioned {
+        fn read(&self, name: &str) -> Result<(String, Option<String>)> {
+            let files = self.files.borrow();
+            Ok((files[name].0.to_owned(), None))
+        }
+
+        fn modified(&self, name: &str) -> Option<SystemTime> {
+            let version = self.files.borrow().get(name)?.1;
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(version))
+        }
+    }
+
+    #[test]
+    fn it_reloads_changed_templates() -> anyhow::Result<()> {
+        let files = Versioned::default();
+        files.write("page", "<box><include src='label'/></box>");
+        files.write("label", "<text>Old</text>");
+        let mut cache = Cache::new(&files);
+
+        let template = cache.load("page")?;
+        files.write("label", "<text>New</text>");
+        assert!(Arc::ptr_eq(&template, &cache.load("page")?));
+
+        assert_eq!(cache.invalidate(), vec!["page"]);
+        assert!(cache.invalidate().is_empty());
+        let elem = cache.load("page")?.render(&Bindings::default())?;
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "New"));
+
+        cache.hot_reload = true;
+        files.write("label", "<text>Newer</text>");
+        let elem = cache.load("page")?.render(&Bindings::default())?;
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "Newer"));
+        assert_eq!(cache.invalidate(), vec!["page"]);
+        assert!(cache.invalidate().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+//! Parsing of CSS color values: hex literals, the `rgb()`, `rgba()`, `hsl()` and `hsla()` functions,
+//! the CSS named colors and the palette registered from script.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::markup::{Error, ErrorKind, Result};
+use crate::tree::Color;
+
+/// Colors registered by name from script, they take precedence over the HUD and the CSS named colors.
+static PALETTE: Mutex<BTreeMap<String, Color>> = Mutex::new(BTreeMap::new());
+
+/// Registers a named color for use in templates, the value can be given in any of the supported forms.
+pub fn register(name: &str, value: &str) -> Result<()> {
+    let is_valid = !name.is_empty()
+        && !name.starts_with(|ch: char| ch.is_ascii_digit())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    if !is_valid {
+        return Err(Error::new(ErrorKind::InvalidValue(name.to_owned())));
+    }
+    let color = parse(value).ok_or_else(|| Error::new(ErrorKind::InvalidColor(value.to_owned())))?;
+    PALETTE.lock().unwrap().insert(name.to_ascii_lowercase(), color);
+    Ok(())
+}
+
+pub fn parse(str: &str) -> Option<Color> {
+    let str = str.trim();
+    if let Some(hex) = str.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    let str = str.to_ascii_lowercase();
+    if let Some((name, args)) = str.strip_suffix(')').and_then(|str| str.split_once('(')) {
+        return parse_function(name.trim(), args);
+    }
+    if let Some(color) = PALETTE.lock().unwrap().get(&str) {
+        return Some(*color);
+    }
+    if str == "transparent" {
+        return Some(Color::new(0, 0, 0, 0));
+    }
+    let (_, rgb) = HUD.iter().chain(NAMED).find(|(name, _)| *name == str)?;
+    Some(Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 255))
+}
+
+/// Parses hex digits in the `rgb`, `rgba`, `rrggbb` or `rrggbbaa` form.
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|ch| ch.to_digit(16).map(|val| val as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    Some(Color::new(
+        digits[0],
+        digits[1],
+        digits[2],
+        digits.get(3).copied().unwrap_or(255),
+    ))
+}
+
+/// Parses the arguments of a color function, they're separated with commas
+/// or with spaces and a slash before the alpha like in `rgb(255 0 0 / 50%)`.
+fn parse_function(name: &str, args: &str) -> Option<Color> {
+    let args: Vec<&str> = if args.contains(',') {
+        args.split(',').map(str::trim).collect()
+    } else {
+        args.split(|ch: char| ch.is_whitespace() || ch == '/')
+            .filter(|arg| !arg.is_empty())
+            .collect()
+    };
+    let alpha = match args.len() {
+        3 => 255,
+        4 => to_byte(fraction(args[3])?),
+        _ => return None,
+    };
+    match name {
+        "rgb" | "rgba" => {
+            let channel = |arg: &str| match arg.strip_suffix('%') {
+                Some(percent) => Some(to_byte(percent.trim().parse::<f32>().ok()? / 100f32)),
+                None => Some(to_byte(arg.parse::<f32>().ok()? / 255f32)),
+            };
+            Some(Color::new(
+                channel(args[0])?,
+                channel(args[1])?,
+                channel(args[2])?,
+                alpha,
+            ))
+        }
+        "hsl" | "hsla" => {
+            let hue = args[0]
+                .strip_suffix("deg")
+                .unwrap_or(args[0])
+                .trim()
+                .parse::<f32>()
+                .ok()?;
+            let percent = |arg: &str| Some(arg.strip_suffix('%')?.trim().parse::<f32>().ok()? / 100f32);
+            let (red, green, blue) = hsl_to_rgb(hue, percent(args[1])?, percent(args[2])?);
+            Some(Color::new(to_byte(red), to_byte(green), to_byte(blue), alpha))
+        }
+        _ => None,
+    }
+}
+
+/// Parses an alpha value given either as a number between 0 and 1 or as a percentage.
+fn fraction(arg: &str) -> Option<f32> {
+    match arg.strip_suffix('%') {
+        Some(percent) => Some(percent.trim().parse::<f32>().ok()? / 100f32),
+        None => arg.parse::<f32>().ok(),
+    }
+}
+
+fn to_byte(val: f32) -> u8 {
+    (val.clamp(0f32, 1f32) * 255f32).round() as u8
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let saturation = saturation.clamp(0f32, 1f32);
+    let lightness = lightness.clamp(0f32, 1f32);
+    let chroma = (1f32 - (2f32 * lightness - 1f32).abs()) * saturation;
+    let sector = hue.rem_euclid(360f32) / 60f32;
+    let x = chroma * (1f32 - (sector % 2f32 - 1f32).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, x, 0f32),
+        1 => (x, chroma, 0f32),
+        2 => (0f32, chroma, x),
+        3 => (0f32, x, chroma),
+        4 => (x, 0f32, chroma),
+        _ => (chroma, 0f32, x),
+    };
+    let m = lightness - chroma / 2f32;
+    (red + m, green + m, blue + m)
+}
+
+/// The standard colors of the game's HUD from `MainColors` in the base ink styles,
+/// the HDR values are clamped to the displayable range.
+const HUD: &[(&str, u32)] = &[
+    ("hud-red", 0xff6159),
+    ("hud-cyan", 0x5ef6ff),
+    ("hud-yellow", 0xffd741),
+    ("hud-green", 0x1ded83),
+];
+
+const NAMED: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, register};
+    use crate::tree::Color;
+
+    #[test]
+    fn it_parses_css_colors() {
+        assert_eq!(parse("#f00"), Some(Color::new(255, 0, 0, 255)));
+        assert_eq!(parse("#f008"), Some(Color::new(255, 0, 0, 136)));
+        assert_eq!(parse("#00ff00"), Some(Color::new(0, 255, 0, 255)));
+        assert_eq!(parse("#0000ff80"), Some(Color::new(0, 0, 255, 128)));
+        assert_eq!(parse("rgb(255, 128, 0)"), Some(Color::new(255, 128, 0, 255)));
+        assert_eq!(parse("rgba(255, 0, 0, 0.5)"), Some(Color::new(255, 0, 0, 128)));
+        assert_eq!(parse("rgb(100% 0% 0% / 25%)"), Some(Color::new(255, 0, 0, 64)));
+        assert_eq!(parse("hsl(120, 100%, 50%)"), Some(Color::new(0, 255, 0, 255)));
+        assert_eq!(parse("hsla(240deg 100% 50% / 0)"), Some(Color::new(0, 0, 255, 0)));
+        assert_eq!(parse("CornflowerBlue"), Some(Color::new(100, 149, 237, 255)));
+        assert_eq!(parse("transparent"), Some(Color::new(0, 0, 0, 0)));
+
+        for invalid in ["#ff00f", "#gg0000", "rgb(1, 2)", "hsl(0, 50, 50%)", "reddish", ""] {
+            assert_eq!(parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn it_resolves_palette_colors() {
+        assert_eq!(parse("test-red"), None);
+        register("test-red", "#ff3a3a").unwrap();
+        register("test-cyan", "rgba(94, 246, 255, 0.8)").unwrap();
+        assert_eq!(parse("Test-Red"), Some(Color::new(255, 58, 58, 255)));
+        assert_eq!(parse("test-cyan"), Some(Color::new(94, 246, 255, 204)));
+
+        assert_eq!(parse("hud-red"), Some(Color::new(255, 97, 89, 255)));
+        assert_eq!(parse("HUD-Cyan"), Some(Color::new(94, 246, 255, 255)));
+
+        assert!(register("test-blue", "not a color").is_err());
+        assert!(register("#blue", "#00f").is_err());
+    }
+}
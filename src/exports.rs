@@ -1,34 +1,350 @@
-use flexlayout_rs::Dimension;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use flexlayout_rs::NodeWithLayout;
 use red4ext_rs::interop::Vector2;
 use red4ext_rs::prelude::*;
 
-use crate::redscript::{DimensionUnit, Elem, Widget};
-use crate::{layout, markup};
+use crate::layout::Built;
+use crate::markup::Template;
+use crate::metrics::{self, FontMetrics};
+use crate::redscript::{self, Bindings, Elem, LayoutRect, RenderResult, TemplateListener, Widget};
+use crate::{cache, color, layout, markup, packed, session, shape, tree};
+
+static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+static ENUMS_CHECKED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static TEMPLATES: RefCell<Templates> = RefCell::new(Templates::default());
+}
+
+#[derive(Default)]
+struct Templates {
+    next_id: u64,
+    templates: HashMap<u64, Compiled>,
+    listeners: Vec<TemplateListener>,
+}
+
+#[derive(Clone)]
+enum Compiled {
+    Inline(Arc<Template>),
+    // templates loaded from files are looked up in the cache on every render to pick up changes
+    File(String),
+}
 
 pub fn render_elem(root: Elem, size: Vector2) -> Widget {
-    let node = layout::build(root);
+    layout::render(lay_out(root, size), &mut |_, _, _| {})
+}
 
+/// Computes the layout of the tree without rendering it.
+pub fn compute_layout(root: Elem, size: Vector2) -> LayoutRect {
+    let computed = layout::compute(lay_out(root, size));
+    LayoutRect::from_computed(&computed)
+}
+
+/// Renders the tree like `render_elem` and records the widget and the rect of every element with an id.
+pub fn render_elem_with_result(root: Elem, size: Vector2) -> RenderResult {
+    let layout = lay_out(root, size);
+    let mut rendered = vec![];
+    let widget = layout::render(layout, &mut |elem, widget, rect| {
+        if let Some(id) = elem.id() {
+            rendered.push((id, widget.clone(), rect));
+        }
+    });
+    let res = RenderResult::new(&widget);
+    for (id, widget, rect) in &rendered {
+        res.add(id, widget, *rect);
+    }
+    res
+}
+
+/// Lays out the tree in the size given, a zero width or height leaves it unconstrained.
+fn lay_out(root: Elem, size: Vector2) -> NodeWithLayout<Built<Elem>> {
+    check_enums_once();
     let width = if size.x > 0f32 { Some(size.x) } else { None };
     let height = if size.y > 0f32 { Some(size.y) } else { None };
+    layout::lay_out(root, width, height)
+}
+
+/// Compares reading the layout of every element in the tree one property at a time
+/// against the packed transfer, the report is meant to be printed from script.
+pub fn benchmark_layout_transfer(root: Elem, iterations: i32) -> String {
+    fn collect(elem: Elem, out: &mut Vec<Elem>) {
+        for child in elem.children() {
+            collect(child, out);
+        }
+        out.push(elem);
+    }
+    let mut elems = vec![];
+    collect(root, &mut elems);
 
-    let layout = node.layout(width, height);
-    layout::render(layout)
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for elem in &elems {
+            elem.layout().read_fields();
+        }
+    }
+    let per_field = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for elem in &elems {
+            elem.layout_data();
+        }
+    }
+    let packed = start.elapsed();
+
+    format!(
+        "{} elements x {} iterations: per-field {:?}, packed {:?}",
+        elems.len(),
+        iterations,
+        per_field,
+        packed
+    )
 }
 
-pub fn parse_dimension(str: String) -> Ref<RED4ext::IScriptable> {
-    let (val, unit) = match layout::parse_dimension(&str).unwrap() {
-        Dimension::Auto => (0f32, DimensionUnit::Auto),
-        Dimension::Point(val) => (val, DimensionUnit::Point),
-        Dimension::Percent(val) => (val, DimensionUnit::Percent),
-    };
+pub fn create_render_tree(root: Elem, size: Vector2) -> u64 {
+    check_enums_once();
+    session::create(root, size)
+}
 
-    call!("Flexy.Layout.Dim::New;FloatUnit" (val, unit) -> Ref<RED4ext::IScriptable>)
+pub fn get_render_tree_widget(id: u64) -> Widget {
+    session::widget(id)
+}
+
+pub fn get_render_tree_widget_by_id(id: u64, elem_id: String) -> Widget {
+    session::widget_by_id(id, &elem_id)
+}
+
+pub fn relayout_render_tree(id: u64, size: Vector2) {
+    session::relayout(id, size)
+}
+
+pub fn release_render_tree(id: u64) {
+    session::release(id)
+}
+
+pub fn mark_elem_dirty(elem: Elem) {
+    session::mark_dirty(&elem)
+}
+
+pub fn measure_text(text: String, font_size: i32, max_width: f32) -> Vector2 {
+    let max_width = if max_width >= 0f32 { Some(max_width) } else { None };
+    let font = FontMetrics::for_family(metrics::DEFAULT_FONT_FAMILY);
+    let (width, height) = font.measure(&text, font_size as f32, max_width);
+    Vector2::new(width, height)
+}
+
+/// Returns the outline of a rounded rectangle for `inkShape`, see `shape::rounded_rect`.
+pub fn rounded_rect_vertices(size: Vector2, radius: f32, inset: f32) -> Vec<Vector2> {
+    shape::rounded_rect(size.x, size.y, radius, inset)
+        .into_iter()
+        .map(|(x, y)| Vector2::new(x, y))
+        .collect()
+}
+
+pub fn wrap_text(text: String, font_size: i32, max_width: f32) -> String {
+    let font = FontMetrics::for_family(metrics::DEFAULT_FONT_FAMILY);
+    font.wrap(&text, font_size as f32, Some(max_width)).join("\n")
+}
+
+pub fn parse_dimension(str: String) -> Ref<RED4ext::IScriptable> {
+    match layout::parse_dimension(&str) {
+        Ok(dim) => {
+            *LAST_ERROR.lock().unwrap() = None;
+            redscript::new_dim(dim)
+        }
+        Err(err) => {
+            report_error(format!("Invalid dimension literal '{}': {}", str, err));
+            Ref::default()
+        }
+    }
 }
 
 pub fn parse_markup(input: String) -> Elem {
-    markup::parse(&input).unwrap()
+    lower_markup(markup::parse(&input), &Ref::default(), "<inline>")
 }
 
 pub fn load_markup(name: String) -> Elem {
-    markup::load(&name).unwrap()
+    lower_markup(markup::load(&name), &Ref::default(), &name)
+}
+
+/// Registers a script class extending `Elem` to be rendered for a custom tag in templates.
+pub fn register_tag(name: String, class_name: String) -> bool {
+    match markup::register_tag(&name, &class_name) {
+        Ok(()) => true,
+        Err(err) => {
+            report_error(format!("Failed to register tag {}: {}", name, err));
+            false
+        }
+    }
+}
+
+/// Verifies that the enums declared in script match the values expected by the plugin,
+/// every mismatch is logged.
+/// Verifies the enums shared with script, it's called by script once the scripts are loaded.
+pub fn check_enums() -> bool {
+    ENUMS_CHECKED.store(true, Ordering::Relaxed);
+    let mismatches = packed::check_enums(&redscript::describe_enums());
+    for mismatch in &mismatches {
+        redscript::log(&format!("[Flexy] Enum mismatch: {}", mismatch));
+    }
+    mismatches.is_empty()
+}
+
+// scripts are not loaded yet when the plugin is registered, the check is started by a scriptable
+// system when they are, and otherwise runs before the first tree is lowered or rendered
+fn check_enums_once() {
+    if !ENUMS_CHECKED.load(Ordering::Relaxed) {
+        check_enums();
+    }
+}
+
+pub fn parse_markup_with(input: String, bindings: Bindings) -> Elem {
+    let res = Template::parse(&input).and_then(|template| template.render(&bindings.read()));
+    lower_markup(res, &bindings.target(), "<inline>")
+}
+
+pub fn load_markup_with(name: String, bindings: Bindings) -> Elem {
+    let res = cache::load(&name).and_then(|template| template.render(&bindings.read()));
+    lower_markup(res, &bindings.target(), &name)
+}
+
+/// Parses a template and keeps it for rendering with different bindings, returns 0 on failure.
+pub fn compile_template(input: String) -> u64 {
+    let res = Template::parse(&input).map(|template| Compiled::Inline(Arc::new(template)));
+    store_template(res, "<inline>")
+}
+
+pub fn compile_template_file(name: String) -> u64 {
+    let res = cache::load(&name).map(|_| Compiled::File(name.clone()));
+    store_template(res, &name)
+}
+
+pub fn render_template(id: u64, bindings: Bindings) -> Elem {
+    let compiled = TEMPLATES.with(|templates| templates.borrow().templates.get(&id).cloned());
+    match compiled {
+        Some(Compiled::Inline(template)) => {
+            lower_markup(template.render(&bindings.read()), &bindings.target(), "template")
+        }
+        Some(Compiled::File(name)) => {
+            let res = cache::load(&name).and_then(|template| template.render(&bindings.read()));
+            lower_markup(res, &bindings.target(), &name)
+        }
+        None => {
+            report_error(format!("Unknown template {}", id));
+            Elem::default()
+        }
+    }
+}
+
+pub fn release_template(id: u64) {
+    TEMPLATES.with(|templates| templates.borrow_mut().templates.remove(&id));
+}
+
+fn store_template(res: markup::Result<Compiled>, source: &str) -> u64 {
+    match res {
+        Ok(template) => {
+            *LAST_ERROR.lock().unwrap() = None;
+            TEMPLATES.with(|templates| {
+                let mut templates = templates.borrow_mut();
+                templates.next_id += 1;
+                let id = templates.next_id;
+                templates.templates.insert(id, template);
+                id
+            })
+        }
+        Err(err) => {
+            report_error(format!("Failed to parse markup {}: {}", source, err));
+            0
+        }
+    }
+}
+
+/// Registers a named color that can be used in templates in place of a color literal.
+pub fn register_color(name: String, value: String) -> bool {
+    match color::register(&name, &value) {
+        Ok(()) => true,
+        Err(err) => {
+            report_error(format!("Failed to register color {}: {}", name, err));
+            false
+        }
+    }
+}
+
+/// Registers the directory of a mod's templates, they're loaded with names like `mymod:hud/panel`.
+pub fn add_template_root(namespace: String, path: String) -> bool {
+    match cache::add_root(&namespace, &path) {
+        Ok(()) => true,
+        Err(err) => {
+            report_error(format!("Failed to add template root {}: {}", path, err));
+            false
+        }
+    }
+}
+
+/// Makes loading a template check whether its files have changed since it was cached.
+pub fn set_hot_reload(enabled: bool) {
+    cache::set_hot_reload(enabled)
+}
+
+/// Drops the cached templates whose files have changed and notifies the listeners about each of them
+/// and about the templates reloaded by hot reload since, returns the number of templates reported.
+pub fn reload_templates() -> i32 {
+    let changed = cache::invalidate();
+    let listeners = TEMPLATES.with(|templates| templates.borrow().listeners.clone());
+    for name in &changed {
+        for listener in &listeners {
+            listener.on_template_changed(name);
+        }
+    }
+    changed.len() as i32
+}
+
+/// Reloads the templates while hot reload is enabled and anyone is listening,
+/// it's called periodically by the template watcher in script.
+pub fn poll_templates() {
+    let has_listeners = TEMPLATES.with(|templates| !templates.borrow().listeners.is_empty());
+    if has_listeners && cache::is_hot_reload_enabled() {
+        reload_templates();
+    }
+}
+
+pub fn add_template_listener(listener: TemplateListener) {
+    TEMPLATES.with(|templates| templates.borrow_mut().listeners.push(listener));
+}
+
+pub fn remove_template_listener(listener: TemplateListener) {
+    TEMPLATES.with(|templates| {
+        templates
+            .borrow_mut()
+            .listeners
+            .retain(|other| other.repr.instance != listener.repr.instance)
+    });
+}
+
+pub fn get_last_error() -> String {
+    LAST_ERROR.lock().unwrap().clone().unwrap_or_default()
+}
+
+fn lower_markup(res: markup::Result<tree::Elem>, target: &Ref<RED4ext::IScriptable>, source: &str) -> Elem {
+    check_enums_once();
+    match res {
+        Ok(elem) => {
+            *LAST_ERROR.lock().unwrap() = None;
+            Elem::from_tree(&elem, target)
+        }
+        Err(err) => {
+            report_error(format!("Failed to parse markup {}: {}", source, err));
+            Elem::default()
+        }
+    }
+}
+
+fn report_error(msg: String) {
+    redscript::log(&format!("[Flexy] {}", msg));
+    *LAST_ERROR.lock().unwrap() = Some(msg);
 }
@@ -1,25 +1,513 @@
 use anyhow::{anyhow, Result};
-use flexlayout_rs::{Dimension, Node, NodeWithLayout};
+use flexlayout_rs::{Dimension, FlexDirection, FlexProperty, FlexWrap, Node, NodeWithLayout};
 use red4ext_rs::interop::Vector2;
 use red4ext_rs::prelude::*;
 
-use crate::redscript;
+use crate::metrics::{self, FontMetrics};
+use crate::{redscript, tree};
 
-pub fn build(elem: redscript::Elem) -> Node<redscript::Elem> {
-    let children = elem.children().into_iter().map(build).collect();
-    let props = elem.layout().properties();
-    Node::new(children, props, elem)
+pub trait LayoutElem: Sized {
+    fn children(&self) -> Vec<Self>;
+    fn layout(&self) -> tree::Layout;
+    /// Returns the intrinsic size of a leaf element, wrapping to `max_width` where applicable.
+    fn measure(&self, max_width: Option<f32>) -> Option<(f32, f32)>;
 }
 
-pub fn render(node: NodeWithLayout<redscript::Elem>) -> redscript::Widget {
-    let pos = Vector2::new(node.left(), node.top());
-    let size = Vector2::new(node.width(), node.height());
-    let elem = node.inner().context();
+impl LayoutElem for redscript::Elem {
+    #[inline]
+    fn children(&self) -> Vec<Self> {
+        redscript::Elem::children(self)
+    }
+
+    #[inline]
+    fn layout(&self) -> tree::Layout {
+        self.layout_data()
+    }
+
+    fn measure(&self, max_width: Option<f32>) -> Option<(f32, f32)> {
+        let size = redscript::Elem::measure(self, max_width);
+        if size.x > 0f32 || size.y > 0f32 {
+            Some((size.x, size.y))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> LayoutElem for &'a tree::Elem {
+    #[inline]
+    fn children(&self) -> Vec<Self> {
+        self.children.iter().collect()
+    }
+
+    #[inline]
+    fn layout(&self) -> tree::Layout {
+        self.layout.clone()
+    }
+
+    fn measure(&self, max_width: Option<f32>) -> Option<(f32, f32)> {
+        match &self.kind {
+            tree::ElemKind::Text { text, font_size, .. } => {
+                let font_size = font_size.unwrap_or(metrics::DEFAULT_FONT_SIZE) as f32;
+                let font = FontMetrics::for_family(metrics::DEFAULT_FONT_FAMILY);
+                Some(font.measure(text, font_size, max_width))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An element of the layout tree together with the layout it was laid out with.
+pub struct Built<E> {
+    pub elem: E,
+    pub layout: tree::Layout,
+    /// The size a leaf was measured at, with the height it wraps to at its final width.
+    pub measured: Option<(f32, f32)>,
+}
+
+/// The most passes [lay_out] makes over a tree.
+pub const MAX_PASSES: usize = 3;
+
+/// Lays out the tree. Leaves are measured before they're grown or shrunk, so once they are,
+/// they're measured again at the width they were laid out at, and the tree is laid out again
+/// with the heights they wrap to if any of them has changed.
+pub fn lay_out<E: LayoutElem + Clone>(
+    elem: E,
+    width: Option<f32>,
+    height: Option<f32>,
+) -> NodeWithLayout<Built<E>> {
+    let mut heights = Heights::default();
+    let mut pass = 1;
+    loop {
+        let layout = elem.layout();
+        let node = build_with_layout(
+            elem.clone(),
+            layout,
+            width,
+            None,
+            tree::Edges::default(),
+            Some(&heights),
+        );
+        let res = node.layout(width, height);
+        if pass == MAX_PASSES || !remeasure(&res, &mut heights) {
+            return res;
+        }
+        pass += 1;
+    }
+}
+
+/// The heights of the leaves at the widths they were last laid out at,
+/// children are stored in the order of the displayed ones.
+#[derive(Default)]
+struct Heights {
+    height: Option<f32>,
+    children: Vec<Heights>,
+}
+
+/// Builds the layout tree, leaf elements with an automatic size are measured up front
+/// against the width available from their closest ancestor with a known width.
+pub fn build<E: LayoutElem>(elem: E, available_width: Option<f32>) -> Node<Built<E>> {
+    let layout = elem.layout();
+    build_with_layout(elem, layout, available_width, None, tree::Edges::default(), None)
+}
+
+fn build_with_layout<E: LayoutElem>(
+    elem: E,
+    mut layout: tree::Layout,
+    available_width: Option<f32>,
+    parent: Option<&tree::Layout>,
+    spacing: tree::Edges,
+    heights: Option<&Heights>,
+) -> Node<Built<E>> {
+    apply_overflow(&mut layout, parent);
+    resolve_percentages(&mut layout, parent, available_width);
+    let inner_width = inner_width(&layout, spacing, available_width);
+    let children: Vec<_> = elem
+        .children()
+        .into_iter()
+        .map(|child| {
+            let layout = child.layout();
+            (child, layout)
+        })
+        .filter(|(_, layout)| layout.display != tree::Display::None)
+        .collect();
+
+    let mut measured = None;
+    if children.is_empty() {
+        let wrapped = heights.and_then(|heights| heights.height);
+        measured = elem
+            .measure(inner_width)
+            .map(|(width, height)| (width, wrapped.unwrap_or(height)));
+        apply_intrinsic_size(&mut layout, measured, parent);
+    }
+
+    let child_layouts: Vec<_> = children.iter().map(|(_, layout)| layout).collect();
+    let gaps = gap_margins(&layout, &child_layouts);
+    let children = children
+        .into_iter()
+        .zip(gaps)
+        .enumerate()
+        .map(|(idx, ((child, child_layout), gap))| {
+            let heights = heights.and_then(|heights| heights.children.get(idx));
+            build_with_layout(child, child_layout, inner_width, Some(&layout), gap, heights)
+        })
+        .collect();
+    Node::new(children, flex_properties(&layout, spacing), Built {
+        elem,
+        layout,
+        measured,
+    })
+}
+
+/// Measures the leaves again at the widths they were laid out at and records their heights,
+/// returns whether any of them is different from the height the leaf was laid out with.
+fn remeasure<E: LayoutElem>(node: &NodeWithLayout<Built<E>>, heights: &mut Heights) -> bool {
+    let Built {
+        elem,
+        layout,
+        measured,
+    } = node.inner().context();
+    let children: Vec<_> = node.children().into_iter().collect();
+    if !children.is_empty() {
+        heights.children.resize_with(children.len(), Heights::default);
+        return children
+            .iter()
+            .zip(&mut heights.children)
+            .fold(false, |changed, (child, heights)| {
+                remeasure(child, heights) || changed
+            });
+    }
+    let measured_height = match measured {
+        Some((_, height)) => *height,
+        None => return false,
+    };
+    let width = (node_rect(node, layout)[2] - horizontal_inset(layout)).max(0f32);
+    let height = elem
+        .measure(Some(width))
+        .map_or(measured_height, |(_, height)| height);
+    heights.height = Some(height);
+    height_changed(measured_height, height)
+}
+
+/// Returns whether a leaf measured again has a height different enough to be laid out again.
+pub fn height_changed(height: f32, new_height: f32) -> bool {
+    (height - new_height).abs() > 0.01
+}
+
+/// Returns the margins that emulate the gaps of a container when added to its children,
+/// flexlayout has no gaps of its own. In a single line the gap goes before every child but the first,
+/// in a wrapping container it goes after every child and the container is laid out larger by a gap
+/// in both directions, so that the last child of each line ends at the edge: the padding is given up
+/// first, see [layout_padding], and what's left of the gaps is added to the size, see [gap_excess].
+/// Absolute children are moved back by the excess so that their insets are kept.
+pub fn gap_margins(layout: &tree::Layout, children: &[&tree::Layout]) -> Vec<tree::Edges> {
+    let (row, column) = (layout.row_gap, layout.column_gap);
+    let mut in_flow = 0;
+    children
+        .iter()
+        .map(|child| {
+            let mut gap = tree::Edges::default();
+            if child.position_type == tree::PositionType::Absolute {
+                (gap.right, gap.bottom) = gap_excess(layout);
+                return gap;
+            }
+            if is_wrapping(layout) {
+                gap.right = column;
+                gap.bottom = row;
+            } else if in_flow > 0 {
+                match layout.flex_direction {
+                    FlexDirection::Row => gap.left = column,
+                    FlexDirection::RowReverse => gap.right = column,
+                    FlexDirection::Column => gap.top = row,
+                    FlexDirection::ColumnReverse => gap.bottom = row,
+                }
+            }
+            in_flow += 1;
+            gap
+        })
+        .collect()
+}
+
+/// Returns the properties of a node with the spacing emulating the gaps, which is left out
+/// of the layout itself so that the margins and the padding read back from it are the declared ones.
+/// A wrapping container is enlarged by its [gap_excess] and takes it back with a negative margin,
+/// so that it takes up the same space in its parent.
+pub fn flex_properties(layout: &tree::Layout, spacing: tree::Edges) -> Vec<FlexProperty> {
+    let mut layout = layout.clone();
+    let (excess_width, excess_height) = gap_excess(&layout);
+    layout.margin.left += spacing.left;
+    layout.margin.right += spacing.right - excess_width;
+    layout.margin.top += spacing.top;
+    layout.margin.bottom += spacing.bottom - excess_height;
+    layout.padding = layout_padding(&layout);
+    for (dim, excess) in [
+        (&mut layout.width, excess_width),
+        (&mut layout.min_width, excess_width),
+        (&mut layout.max_width, excess_width),
+        (&mut layout.height, excess_height),
+        (&mut layout.min_height, excess_height),
+        (&mut layout.max_height, excess_height),
+    ] {
+        if let Dimension::Point(val) = dim {
+            *val += excess;
+        }
+    }
+    layout.properties()
+}
+
+/// Returns the padding the node is laid out with, a wrapping container gives up as much of it
+/// as it can to the gaps after the last child of each line.
+pub fn layout_padding(layout: &tree::Layout) -> tree::Edges {
+    let mut padding = layout.padding;
+    if is_wrapping(layout) {
+        padding.right = (padding.right - layout.column_gap).max(0f32);
+        padding.bottom = (padding.bottom - layout.row_gap).max(0f32);
+    }
+    padding
+}
+
+/// Returns the width and the height a wrapping container is laid out larger by, which is the part
+/// of the gaps after the last child of each line that its padding can't take, see [gap_margins].
+/// A size that's a percentage of an unknown size can't be enlarged, the gap stays within it then.
+pub fn gap_excess(layout: &tree::Layout) -> (f32, f32) {
+    if !is_wrapping(layout) {
+        return (0f32, 0f32);
+    }
+    let excess = |gap: f32, padding: f32, size: Dimension| match size {
+        Dimension::Percent(_) => 0f32,
+        _ => (gap - padding).max(0f32),
+    };
+    (
+        excess(layout.column_gap, layout.padding.right, layout.width),
+        excess(layout.row_gap, layout.padding.bottom, layout.height),
+    )
+}
+
+/// Returns the rect of a laid out node without the [gap_excess] of its layout.
+fn node_rect<C>(node: &NodeWithLayout<C>, layout: &tree::Layout) -> [f32; 4] {
+    let (excess_width, excess_height) = gap_excess(layout);
+    [
+        node.left(),
+        node.top(),
+        node.width() - excess_width,
+        node.height() - excess_height,
+    ]
+}
+
+/// Resolves the percentages flexlayout would resolve against a box enlarged by the gaps, see
+/// [gap_excess]: the sizes of the items of a wrapping container and the width of the container itself.
+pub fn resolve_percentages(
+    layout: &mut tree::Layout,
+    parent: Option<&tree::Layout>,
+    available_width: Option<f32>,
+) {
+    fn resolve(dim: &mut Dimension, size: Option<f32>) {
+        if let (Dimension::Percent(val), Some(size)) = (*dim, size) {
+            *dim = Dimension::Point(size * val / 100f32);
+        }
+    }
+
+    if is_wrapping(layout) {
+        resolve(&mut layout.width, available_width);
+        resolve(&mut layout.min_width, available_width);
+        resolve(&mut layout.max_width, available_width);
+    }
+    let in_flow = layout.position_type == tree::PositionType::Relative;
+    let parent = match parent.filter(|parent| in_flow && is_wrapping(parent)) {
+        Some(parent) => parent,
+        None => return,
+    };
+    let available_height = match parent.height {
+        Dimension::Point(height) => Some(
+            height - parent.padding.top - parent.padding.bottom - parent.border.top - parent.border.bottom,
+        ),
+        _ => None,
+    };
+    resolve(&mut layout.width, available_width);
+    resolve(&mut layout.min_width, available_width);
+    resolve(&mut layout.max_width, available_width);
+    resolve(&mut layout.height, available_height);
+    resolve(&mut layout.min_height, available_height);
+    resolve(&mut layout.max_height, available_height);
+    let is_row = matches!(
+        parent.flex_direction,
+        FlexDirection::Row | FlexDirection::RowReverse
+    );
+    resolve(
+        &mut layout.flex_basis,
+        if is_row { available_width } else { available_height },
+    );
+}
+
+fn is_wrapping(layout: &tree::Layout) -> bool {
+    !matches!(layout.flex_wrap, FlexWrap::NoWrap)
+}
+
+/// Children of scroll containers don't shrink so that they can extend past the viewport.
+pub fn apply_overflow(layout: &mut tree::Layout, parent: Option<&tree::Layout>) {
+    if parent.is_some_and(|parent| parent.overflow == tree::Overflow::Scroll) {
+        layout.flex_shrink = 0f32;
+    }
+}
+
+/// Returns the size of the content of an element, which covers its children with their margins
+/// and its own padding and border, it's at least the size of the element.
+pub fn content_size(
+    layout: &tree::Layout,
+    size: (f32, f32),
+    children: impl IntoIterator<Item = ([f32; 4], tree::Edges)>,
+) -> (f32, f32) {
+    let (mut width, mut height) = size;
+    for ([left, top, child_width, child_height], margin) in children {
+        let right = left + child_width + margin.right + layout.padding.right + layout.border.right;
+        let bottom = top + child_height + margin.bottom + layout.padding.bottom + layout.border.bottom;
+        width = width.max(right);
+        height = height.max(bottom);
+    }
+    (width, height)
+}
+
+/// Applies the measured size of a leaf with an automatic size. flexlayout can't measure leaves
+/// while laying them out, so for an item in a flex container the size is only a starting point:
+/// it becomes the flex basis along the main axis, where the item can still grow and shrink,
+/// and the minimum size across it, where the item can still be stretched.
+pub fn apply_intrinsic_size(
+    layout: &mut tree::Layout,
+    size: Option<(f32, f32)>,
+    parent: Option<&tree::Layout>,
+) {
+    let parent = parent.filter(|_| layout.position_type == tree::PositionType::Relative);
+    match (size, parent) {
+        (Some((width, height)), Some(parent)) => {
+            let is_row = matches!(
+                parent.flex_direction,
+                FlexDirection::Row | FlexDirection::RowReverse
+            );
+            let (main, main_size, cross, cross_size, min_cross) = if is_row {
+                (layout.width, width, layout.height, height, &mut layout.min_height)
+            } else {
+                (layout.height, height, layout.width, width, &mut layout.min_width)
+            };
+            if matches!(cross, Dimension::Auto) && matches!(min_cross, Dimension::Auto) {
+                *min_cross = Dimension::Point(cross_size);
+            }
+            if matches!(main, Dimension::Auto) && matches!(layout.flex_basis, Dimension::Auto) {
+                layout.flex_basis = Dimension::Point(main_size);
+            }
+        }
+        (Some(size), None) => apply_fixed_size(layout, size),
+        (None, _) => {}
+    }
+}
+
+fn apply_fixed_size(layout: &mut tree::Layout, (width, height): (f32, f32)) {
+    if matches!(layout.width, Dimension::Auto) {
+        layout.width = Dimension::Point(width);
+    }
+    if matches!(layout.height, Dimension::Auto) {
+        layout.height = Dimension::Point(height);
+    }
+}
+
+/// Returns the width available to the children of an element with this layout, if it's known,
+/// the spacing is the one emulating the gaps of the parent.
+pub fn inner_width(layout: &tree::Layout, spacing: tree::Edges, available_width: Option<f32>) -> Option<f32> {
+    let resolve = |dim: Dimension| match dim {
+        Dimension::Point(val) => Some(val),
+        Dimension::Percent(val) => available_width.map(|width| width * val / 100f32),
+        Dimension::Auto => None,
+    };
+    let outer = match resolve(layout.width) {
+        Some(width) => Some(width),
+        None => available_width
+            .map(|width| width - layout.margin.left - layout.margin.right - spacing.left - spacing.right),
+    };
+    let outer = match (outer, resolve(layout.max_width)) {
+        (Some(width), Some(max)) => Some(width.min(max)),
+        (width, max) => width.or(max),
+    };
+    outer.map(|width| (width - horizontal_inset(layout)).max(0f32))
+}
+
+/// Returns the padding and the border on the left and the right of an element.
+pub fn horizontal_inset(layout: &tree::Layout) -> f32 {
+    layout.padding.left + layout.padding.right + layout.border.left + layout.border.right
+}
+
+/// A rectangle relative to the parent element.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// The computed geometry of an element, the content box excludes the padding and the border
+/// and is relative to the element itself.
+#[derive(Debug, Clone)]
+pub struct ComputedLayout<E> {
+    pub elem: E,
+    pub rect: Rect,
+    pub content: Rect,
+    pub children: Vec<ComputedLayout<E>>,
+}
+
+/// Collects the computed geometry of the tree, elements left out of the layout are not included.
+pub fn compute<E: Clone>(node: NodeWithLayout<Built<E>>) -> ComputedLayout<E> {
+    let Built { elem, layout, .. } = node.inner().context();
+    let elem = elem.clone();
+    let rect = node_rect(&node, layout);
+    let rect = Rect {
+        left: rect[0],
+        top: rect[1],
+        width: rect[2],
+        height: rect[3],
+    };
+    let left = layout.padding.left + layout.border.left;
+    let top = layout.padding.top + layout.border.top;
+    let right = layout.padding.right + layout.border.right;
+    let bottom = layout.padding.bottom + layout.border.bottom;
+    let content = Rect {
+        left,
+        top,
+        width: (rect.width - left - right).max(0f32),
+        height: (rect.height - top - bottom).max(0f32),
+    };
+    let children = node.children().into_iter().map(compute).collect();
+    ComputedLayout {
+        elem,
+        rect,
+        content,
+        children,
+    }
+}
+
+/// Renders the widgets of the tree, the callback is invoked with every element and its widget
+/// before the widgets of its children are rendered.
+pub fn render<F>(node: NodeWithLayout<Built<redscript::Elem>>, on_render: &mut F) -> redscript::Widget
+where
+    F: FnMut(&redscript::Elem, &redscript::Widget, [f32; 4]),
+{
+    let Built { elem, layout, .. } = node.inner().context();
+    let rect = node_rect(&node, layout);
+    let pos = Vector2::new(rect[0], rect[1]);
+    let size = Vector2::new(rect[2], rect[3]);
     let widget = call!(elem.repr.clone(), "Render" (pos, size) -> redscript::Widget);
+    elem.apply_appearance(&widget);
+    elem.bind_handlers(&widget, true);
+    on_render(elem, &widget, rect);
 
+    let mut extents = vec![];
     for child in node.children() {
-        let child_widget = render(child);
-        call!(widget.repr.clone(), "AddChildWidget" (child_widget) -> ());
+        let child_layout = &child.inner().context().layout;
+        extents.push((node_rect(&child, child_layout), child_layout.margin));
+        let child_widget = render(child, on_render);
+        elem.attach_child(&widget, &child_widget);
+    }
+    if layout.overflow != tree::Overflow::Visible {
+        let (width, height) = content_size(layout, (rect[2], rect[3]), extents);
+        elem.set_content_size(Vector2::new(width, height));
     }
 
     widget
@@ -46,7 +534,9 @@ pub fn parse_dimension(str: &str) -> Result<Dimension> {
 mod tests {
     use flexlayout_rs::Dimension;
 
-    use super::parse_dimension;
+    use super::{build, compute, content_size, lay_out, parse_dimension, Rect};
+    use crate::markup;
+    use crate::metrics::{self, FontMetrics};
 
     #[test]
     fn it_parses_percentages() {
@@ -57,4 +547,337 @@ mod tests {
     fn it_parses_points() {
         assert!(matches!(parse_dimension("320pt"), Ok(Dimension::Point(v)) if v == 320.0));
     }
+
+    #[test]
+    fn it_lays_out_parsed_markup() -> anyhow::Result<()> {
+        let root =
+            markup::parse("<box width='200pt' height='100pt'><box width='50%' height='20pt'></box></box>")?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+        assert_eq!(layout.width(), 200.0);
+        assert_eq!(layout.height(), 100.0);
+
+        let child = layout.children().into_iter().next().unwrap();
+        assert_eq!(child.width(), 100.0);
+        assert_eq!(child.height(), 20.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_shrinks_and_aligns_items() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='50pt' align-items='start'>\
+               <box flex-basis='80pt' flex-shrink='0' height='10pt'></box>\
+               <box flex-basis='80pt' height='10pt' align-self='end'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].width(), 80.0);
+        assert_eq!(children[1].width(), 20.0);
+        assert_eq!(children[0].top(), 0.0);
+        assert_eq!(children[1].top(), 40.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_clamps_to_size_constraints() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='200pt' height='100pt'>\
+               <box flex-grow='1' max-width='50%'></box>\
+               <box min-width='30pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].width(), 100.0);
+        assert_eq!(children[1].width(), 30.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_measures_text() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' padding='10' flex-direction='column' align-items='start'>\
+               <text font-size='10'>Hello</text>\
+               <text font-size='10'>some long text that has to wrap</text>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let font = FontMetrics::for_family(metrics::DEFAULT_FONT_FAMILY);
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].width(), font.text_width("Hello", 10.0));
+        assert!(children[0].height() > 0.0);
+        assert!(children[1].width() <= 80.0);
+        assert!(children[1].height() > children[0].height());
+        Ok(())
+    }
+
+    #[test]
+    fn it_lets_measured_text_shrink_and_grow() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' align-items='start'>\
+               <box width='60pt' flex-shrink='0' height='10pt'></box>\
+               <text font-size='10'>some long text that has to wrap</text>\
+             </box>",
+        )?;
+        let layout = lay_out(&root, None, None);
+        let text = layout.children().into_iter().nth(1).unwrap();
+        let font = FontMetrics::for_family(metrics::DEFAULT_FONT_FAMILY);
+        let (_, wrapped) = font.measure("some long text that has to wrap", 10.0, Some(40.0));
+        assert_eq!(text.width(), 40.0);
+        assert_eq!(text.height(), wrapped);
+        assert!(
+            wrapped
+                > font
+                    .measure("some long text that has to wrap", 10.0, Some(100.0))
+                    .1
+        );
+
+        let root = markup::parse(
+            "<box width='100pt' height='40pt'>\
+               <text font-size='10' flex-grow='1'>Hello</text>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+        let text = layout.children().into_iter().next().unwrap();
+        assert_eq!(text.width(), 100.0);
+        assert_eq!(text.height(), 40.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_insets_children_by_borders() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='50pt' padding='5' border-width='2 4'>\
+               <box flex-grow='1' height='10pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let child = layout.children().into_iter().next().unwrap();
+        assert_eq!(child.left(), 9.0);
+        assert_eq!(child.top(), 7.0);
+        assert_eq!(child.width(), 82.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_computes_content_boxes() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='50pt' padding='5' border-width='1'>\
+               <box width='20pt' height='10pt'></box>\
+               <box display='none'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let computed = compute(node.layout(None, None));
+
+        assert_eq!(computed.rect, Rect {
+            left: 0.0,
+            top: 0.0,
+            width: 100.0,
+            height: 50.0
+        });
+        assert_eq!(computed.content, Rect {
+            left: 6.0,
+            top: 6.0,
+            width: 88.0,
+            height: 38.0
+        });
+        assert_eq!(computed.children.len(), 1);
+        assert!(std::ptr::eq(computed.children[0].elem, &root.children[0]));
+        assert_eq!(computed.children[0].rect, Rect {
+            left: 6.0,
+            top: 6.0,
+            width: 20.0,
+            height: 10.0
+        });
+        assert_eq!(computed.children[0].content, Rect {
+            left: 0.0,
+            top: 0.0,
+            width: 20.0,
+            height: 10.0
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn it_extends_scroll_content_past_the_viewport() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='50pt' padding='5' flex-direction='column' overflow='scroll'>\
+               <box height='30pt' margin-bottom='2'></box>\
+               <box height='30pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].height(), 30.0);
+        assert_eq!(children[1].top(), 37.0);
+        let extents = children.iter().map(|child| {
+            let rect = [child.left(), child.top(), child.width(), child.height()];
+            (rect, child.inner().context().layout.margin)
+        });
+        assert_eq!(content_size(&root.layout, (100.0, 50.0), extents), (100.0, 72.0));
+        Ok(())
+    }
+
+    #[test]
+    fn it_spaces_items_by_gaps() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='50pt' padding='5' column-gap='10' align-items='start'>\
+               <box width='20pt' height='10pt'></box>\
+               <box position='absolute' width='5pt' height='5pt'></box>\
+               <box width='20pt' height='10pt'></box>\
+               <box flex-grow='1' height='10pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].left(), 5.0);
+        assert_eq!(children[2].left(), 35.0);
+        assert_eq!(children[3].left(), 65.0);
+        assert_eq!(children[3].width(), 30.0);
+        assert_eq!(children[0].inner().context().layout.margin.left, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_spaces_wrapped_lines_by_gaps() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='80pt' padding-right='10' padding-bottom='4' flex-wrap='wrap' gap='4 10' \
+                  align-items='start' align-content='start'>\
+               <box width='30pt' height='10pt'></box>\
+               <box width='30pt' height='10pt'></box>\
+               <box width='30pt' height='10pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!((children[0].left(), children[0].top()), (0.0, 0.0));
+        assert_eq!((children[1].left(), children[1].top()), (40.0, 0.0));
+        assert_eq!((children[2].left(), children[2].top()), (0.0, 14.0));
+        assert_eq!(layout.height(), 28.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_resolves_percentages_in_wrapping_containers() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' flex-wrap='wrap' column-gap='10' align-items='start' align-content='start'>\
+               <box width='50%' height='10pt'></box>\
+               <box width='50%' height='10pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children[0].width(), 50.0);
+        assert_eq!(children[1].width(), 50.0);
+        assert_eq!((children[1].left(), children[1].top()), (0.0, 10.0));
+        Ok(())
+    }
+
+    #[test]
+    fn it_fits_wrapped_items_without_padding() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' flex-wrap='wrap' column-gap='10' align-items='start' align-content='start'>\
+               <box width='45%' height='10pt'></box>\
+               <box width='45%' height='10pt'></box>\
+             </box>",
+        )?;
+        let computed = compute(build(&root, None).layout(None, None));
+
+        assert_eq!(computed.rect.width, 100.0);
+        assert_eq!(computed.rect.height, 10.0);
+        assert_eq!(computed.children[1].rect, Rect {
+            left: 55.0,
+            top: 0.0,
+            width: 45.0,
+            height: 10.0
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn it_leaves_no_gap_after_the_last_wrapped_line() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='200pt' flex-direction='column' align-items='start'>\
+               <box width='100pt' flex-wrap='wrap' gap='10' align-items='start' align-content='start'>\
+                 <box width='45pt' height='10pt'></box>\
+                 <box width='45pt' height='10pt'></box>\
+                 <box width='45pt' height='10pt'></box>\
+               </box>\
+               <box width='20pt' height='10pt'></box>\
+             </box>",
+        )?;
+        let computed = compute(build(&root, None).layout(None, None));
+
+        let wrapping = &computed.children[0];
+        assert_eq!((wrapping.rect.width, wrapping.rect.height), (100.0, 30.0));
+        assert_eq!(
+            (wrapping.children[1].rect.left, wrapping.children[1].rect.top),
+            (55.0, 0.0)
+        );
+        assert_eq!(
+            (wrapping.children[2].rect.left, wrapping.children[2].rect.top),
+            (0.0, 20.0)
+        );
+        assert_eq!(computed.children[1].rect.top, 30.0);
+        assert_eq!(computed.rect.height, 40.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_leaves_out_hidden_elements() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='100pt' height='20pt'>\
+               <box display='none' width='40pt'></box>\
+               <box visibility='hidden' opacity='0.5' width='30pt'></box>\
+               <box width='30pt'></box>\
+             </box>",
+        )?;
+        assert_eq!(root.children.len(), 3);
+        assert!(!root.children[1].appearance.visible);
+        assert_eq!(root.children[1].appearance.opacity, 0.5);
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let children: Vec<_> = layout.children().into_iter().collect();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].left(), 0.0);
+        assert_eq!(children[1].left(), 30.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_places_absolute_elements() -> anyhow::Result<()> {
+        let root = markup::parse(
+            "<box width='200pt' height='100pt'>\
+               <box position='absolute' right='10pt' bottom='5pt' width='20pt' height='20pt'></box>\
+             </box>",
+        )?;
+        let node = build(&root, None);
+        let layout = node.layout(None, None);
+
+        let child = layout.children().into_iter().next().unwrap();
+        assert_eq!(child.left(), 170.0);
+        assert_eq!(child.top(), 75.0);
+        Ok(())
+    }
 }
@@ -1,9 +1,19 @@
 use red4ext_rs::prelude::*;
 
+mod binding;
+mod cache;
+mod color;
 mod exports;
 mod layout;
 mod markup;
+mod metrics;
+mod packed;
 mod redscript;
+mod session;
+mod shape;
+mod source;
+mod style;
+mod tree;
 
 define_plugin! {
     name: "flexy-reds",
@@ -11,8 +21,36 @@ define_plugin! {
     version: 0:0:2,
     on_register: {
         register_function!("Flexy.UI.RenderElem", exports::render_elem);
+        register_function!("Flexy.UI.RenderElemWithResult", exports::render_elem_with_result);
+        register_function!("Flexy.UI.ComputeLayout", exports::compute_layout);
+        register_function!("Flexy.UI.CreateRenderTree", exports::create_render_tree);
+        register_function!("Flexy.UI.GetRenderTreeWidget", exports::get_render_tree_widget);
+        register_function!("Flexy.UI.GetRenderTreeWidgetById", exports::get_render_tree_widget_by_id);
+        register_function!("Flexy.UI.RelayoutRenderTree", exports::relayout_render_tree);
+        register_function!("Flexy.UI.ReleaseRenderTree", exports::release_render_tree);
+        register_function!("Flexy.UI.MarkElemDirty", exports::mark_elem_dirty);
+        register_function!("Flexy.UI.MeasureText", exports::measure_text);
+        register_function!("Flexy.UI.WrapText", exports::wrap_text);
+        register_function!("Flexy.UI.RoundedRectVertices", exports::rounded_rect_vertices);
+        register_function!("Flexy.UI.BenchmarkLayoutTransfer", exports::benchmark_layout_transfer);
         register_function!("Flexy.Layout.ParseDim", exports::parse_dimension);
+        register_function!("Flexy.Layout.CheckEnums", exports::check_enums);
         register_function!("Flexy.Markup.ParseElem", exports::parse_markup);
         register_function!("Flexy.Markup.LoadElem", exports::load_markup);
+        register_function!("Flexy.Markup.ParseElemWith", exports::parse_markup_with);
+        register_function!("Flexy.Markup.LoadElemWith", exports::load_markup_with);
+        register_function!("Flexy.Markup.CompileTemplate", exports::compile_template);
+        register_function!("Flexy.Markup.CompileTemplateFile", exports::compile_template_file);
+        register_function!("Flexy.Markup.RenderTemplate", exports::render_template);
+        register_function!("Flexy.Markup.ReleaseTemplate", exports::release_template);
+        register_function!("Flexy.Markup.RegisterTag", exports::register_tag);
+        register_function!("Flexy.Markup.RegisterColor", exports::register_color);
+        register_function!("Flexy.Markup.AddTemplateRoot", exports::add_template_root);
+        register_function!("Flexy.Markup.SetHotReload", exports::set_hot_reload);
+        register_function!("Flexy.Markup.ReloadTemplates", exports::reload_templates);
+        register_function!("Flexy.Markup.PollTemplates", exports::poll_templates);
+        register_function!("Flexy.Markup.AddTemplateListener", exports::add_template_listener);
+        register_function!("Flexy.Markup.RemoveTemplateListener", exports::remove_template_listener);
+        register_function!("Flexy.Markup.GetLastError", exports::get_last_error);
     }
 }
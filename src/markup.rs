@@ -1,172 +1,869 @@
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
-use anyhow::{anyhow, Result};
-use flexlayout_rs::{FlexAlign, FlexDirection, FlexWrap};
-use red4ext_rs::interop::Color;
+use flexlayout_rs::{Dimension, FlexAlign, FlexDirection, FlexWrap};
 
-use crate::redscript::{Elem, Layout, PositionType};
+use crate::binding::{self, Bindings, Scope, Value};
+use crate::source::{self, Source};
+use crate::style::{self, Stylesheet};
+use crate::tree::{
+    Appearance, Color, Display, Edges, Elem, ElemKind, Event, Handler, Layout, Overflow, PositionType
+};
+use crate::{cache, color, layout};
 
-pub fn load(name: &str) -> Result<Elem> {
-    let path = PathBuf::from("r6")
-        .join("ui")
-        .join("templates")
-        .join(name)
-        .with_extension("html");
+pub type Result<A, E = Error> = std::result::Result<A, E>;
+
+/// Tags handled by the renderer itself, they can't be taken by custom elements.
+const BUILTIN_TAGS: &[&str] = &[
+    "box", "img", "text", "if", "else", "for", "slot", "include", "style",
+];
+
+/// Script classes rendered for custom tags, keyed by the tag name.
+static CUSTOM_TAGS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Makes a tag render as an instance of the script class, the class has to extend `Elem`.
+/// Tags have to be registered before the templates using them are loaded,
+/// otherwise a tag with a hyphen in its name is taken for a component.
+pub fn register_tag(name: &str, class: &str) -> Result<()> {
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    if !is_valid || BUILTIN_TAGS.contains(&name) {
+        return Err(Error::new(ErrorKind::ReservedTag(name.to_owned())));
+    }
+    CUSTOM_TAGS
+        .lock()
+        .unwrap()
+        .insert(name.to_owned(), class.to_owned());
+    Ok(())
+}
 
-    parse(&std::fs::read_to_string(path)?)
+fn custom_tag_class(name: &str) -> Option<String> {
+    CUSTOM_TAGS.lock().unwrap().get(name).cloned()
+}
+
+pub fn load(name: &str) -> Result<Elem> {
+    cache::load(name)?.render(&Bindings::default())
 }
 
 pub fn parse(str: &str) -> Result<Elem> {
-    let dom = tl::parse(str, tl::ParserOptions::default())?;
-    let parser = dom.parser();
-
-    let root = dom.children().first().unwrap().get(parser).unwrap();
-    Ok(parse_elem(root, parser)?.unwrap())
-}
-
-fn parse_elem(node: &tl::Node, parser: &tl::Parser) -> Result<Option<Elem>> {
-    match node {
-        tl::Node::Tag(tag) => {
-            let attrs = tag.attributes();
-            match tag.name().as_bytes() {
-                b"box" => {
-                    let children = tag
-                        .children()
-                        .all(parser)
-                        .into_iter()
-                        .filter_map(|child| parse_elem(child, parser).transpose())
-                        .collect::<Result<Vec<_>>>()?;
-                    let color: Option<Color> = attrs.read_attr("background-color").ok();
-
-                    let elem = Elem::new_box(children, color).with_layout(parse_layout(attrs)?);
-                    Ok(Some(elem))
+    Template::parse(str)?.render(&Bindings::default())
+}
+
+/// A parsed template, it can be rendered any number of times with different bindings.
+/// The templates it includes are loaded along with it.
+#[derive(Debug, Clone)]
+pub struct Template {
+    name: Option<String>,
+    root: Node,
+    style: Stylesheet,
+    includes: HashMap<String, Arc<Template>>,
+}
+
+impl Template {
+    /// Loads a template, the rules of a stylesheet with the same name are applied to it if present.
+    pub fn load_from(source: &dyn Source, name: &str) -> Result<Self> {
+        Loader::new(Some(source)).load(source, name)
+    }
+
+    pub fn parse(str: &str) -> Result<Self> {
+        Self::parse_with_style(str, Stylesheet::default())
+    }
+
+    /// Parses a template with the given stylesheet, `<style>` blocks found in the template
+    /// take precedence over its rules. The templates it includes are loaded through the cache.
+    pub fn parse_with_style(str: &str, style: Stylesheet) -> Result<Self> {
+        Loader::new(None).compile(None, str, style)
+    }
+
+    pub fn render(&self, bindings: &Bindings) -> Result<Elem> {
+        let renderer = Renderer {
+            template: self,
+            scope: &Scope::Root(bindings),
+            slot: None,
+        };
+        let mut elems = vec![];
+        renderer
+            .render_all(std::slice::from_ref(&self.root), &HashMap::new(), &mut elems)
+            .map_err(|err| self.locate(err))?;
+        elems
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::EmptyDocument))
+    }
+
+    /// Returns the names of the templates this one was loaded from, including itself.
+    pub fn dependencies(&self) -> BTreeSet<&str> {
+        let mut res: BTreeSet<&str> = self.name.as_deref().into_iter().collect();
+        for include in self.includes.values() {
+            res.extend(include.dependencies());
+        }
+        res
+    }
+
+    fn locate(&self, err: Error) -> Error {
+        match &self.name {
+            Some(name) => err.or_template(name),
+            None => err,
+        }
+    }
+}
+
+/// Loads templates together with the templates they include,
+/// an include reached again while it's still being loaded is reported as a cycle.
+struct Loader<'a> {
+    // inline templates have no source, their includes are loaded through the cache
+    // like the templates loaded by name, so that they see the registered roots
+    source: Option<&'a dyn Source>,
+    stack: Vec<String>,
+    loaded: HashMap<String, Arc<Template>>,
+}
+
+impl<'a> Loader<'a> {
+    fn new(source: Option<&'a dyn Source>) -> Self {
+        Self {
+            source,
+            stack: vec![],
+            loaded: HashMap::new(),
+        }
+    }
+
+    fn load(&mut self, source: &dyn Source, name: &str) -> Result<Template> {
+        if self.stack.iter().any(|loading| loading == name) {
+            let cycle: Vec<&str> = self
+                .stack
+                .iter()
+                .map(String::as_str)
+                .skip_while(|loading| *loading != name)
+                .chain(std::iter::once(name))
+                .collect();
+            return Err(Error::new(ErrorKind::IncludeCycle(cycle.join(" -> "))));
+        }
+
+        let (markup, style) = source.read(name)?;
+        self.stack.push(name.to_owned());
+        let res = style
+            .map(|css| Stylesheet::parse(&css))
+            .transpose()
+            .and_then(|style| self.compile(Some(name), &markup, style.unwrap_or_default()))
+            .map_err(|err| err.or_template(name));
+        self.stack.pop();
+        res
+    }
+
+    /// Loads an included template, names without a namespace refer to the namespace
+    /// of the template including them.
+    fn include(&mut self, name: &str) -> Result<Arc<Template>> {
+        let name = source::qualify(name, self.stack.last().map(String::as_str)).into_owned();
+        if let Some(template) = self.loaded.get(&name) {
+            return Ok(template.clone());
+        }
+        let template = match self.source {
+            Some(source) => Arc::new(self.load(source, &name)?),
+            None => cache::load(&name)?,
+        };
+        self.loaded.insert(name, template.clone());
+        Ok(template)
+    }
+
+    fn compile(&mut self, name: Option<&str>, str: &str, mut style: Stylesheet) -> Result<Template> {
+        let dom = tl::parse(str, tl::ParserOptions::default())
+            .map_err(|err| Error::new(ErrorKind::Syntax(err.to_string())))?;
+        let parser = dom.parser();
+
+        let mut root = None;
+        for handle in dom.children() {
+            if let Some(node) = handle.get(parser) {
+                collect_styles(node, parser, str, &mut style)?;
+                if root.is_none() {
+                    root = Node::lower(node, parser, str);
+                }
+            }
+        }
+        let root = root.ok_or_else(|| Error::new(ErrorKind::EmptyDocument))?;
+
+        let mut includes = HashMap::new();
+        self.resolve_includes(&root, &mut includes)?;
+        Ok(Template {
+            name: name.map(str::to_owned),
+            root,
+            style,
+            includes,
+        })
+    }
+
+    fn resolve_includes(&mut self, node: &Node, includes: &mut HashMap<String, Arc<Template>>) -> Result<()> {
+        if let Node::Tag(tag) = node {
+            if tag.is_include() {
+                let name = tag.include_name().map_err(|err| err.or_position(tag.position))?;
+                if !includes.contains_key(&*name) {
+                    let template = self.include(&name).map_err(|err| err.or_position(tag.position))?;
+                    includes.insert(name.into_owned(), template);
+                }
+            }
+            for child in &tag.children {
+                self.resolve_includes(child, includes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Tag(Tag),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+struct Tag {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+    position: Option<Position>,
+}
+
+impl Node {
+    /// Converts a node of the document, style blocks, comments and whitespace are left out.
+    fn lower(node: &tl::Node, parser: &tl::Parser, source: &str) -> Option<Self> {
+        match node {
+            tl::Node::Tag(tag) if tag.name().as_bytes() == b"style" => None,
+            tl::Node::Tag(tag) => {
+                let attrs = tag
+                    .attributes()
+                    .iter()
+                    .map(|(name, val)| (name.into_owned(), val.map(Cow::into_owned).unwrap_or_default()))
+                    .collect();
+                let children = children(tag, parser)
+                    .into_iter()
+                    .filter_map(|child| Node::lower(child, parser, source))
+                    .collect();
+                Some(Node::Tag(Tag {
+                    name: tag.name().as_utf8_str().into_owned(),
+                    attrs,
+                    children,
+                    position: Position::of_tag(source, tag),
+                }))
+            }
+            tl::Node::Raw(bytes) => {
+                let text = bytes.as_utf8_str();
+                if text.trim().is_empty() {
+                    None
+                } else {
+                    Some(Node::Text(text.into_owned()))
+                }
+            }
+            tl::Node::Comment(_) => None,
+        }
+    }
+}
+
+impl Tag {
+    /// Includes are either explicit or custom tags with a hyphen in their name,
+    /// which refer to the template of the same name in the `components` directory.
+    /// Explicit includes can see the variables of the caller, components only get their attributes.
+    /// Registered custom tags are never taken for components.
+    fn is_include(&self) -> bool {
+        self.name == "include" || (self.name.contains('-') && custom_tag_class(&self.name).is_none())
+    }
+
+    fn include_name(&self) -> Result<Cow<str>> {
+        if self.name == "include" {
+            self.attr("src").map(Cow::Borrowed)
+        } else {
+            Ok(Cow::Owned(format!("components/{}", self.name)))
+        }
+    }
+
+    fn attr(&self, name: &str) -> Result<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.as_str())
+            .ok_or_else(|| Error::new(ErrorKind::MissingAttribute(name.to_owned())))
+    }
+
+    fn text_content(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Tag(tag) => tag.text_content(out),
+                Node::Text(text) => out.push_str(text),
+            }
+        }
+    }
+}
+
+fn collect_styles(node: &tl::Node, parser: &tl::Parser, source: &str, style: &mut Stylesheet) -> Result<()> {
+    if let tl::Node::Tag(tag) = node {
+        if tag.name().as_bytes() == b"style" {
+            style
+                .append(&tag.inner_text(parser))
+                .map_err(|err| err.or_position(Position::of_tag(source, tag)))?;
+        } else {
+            for child in children(tag, parser) {
+                collect_styles(child, parser, source, style)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the direct children of a tag.
+fn children<'a>(tag: &tl::HTMLTag<'a>, parser: &'a tl::Parser<'a>) -> Vec<&'a tl::Node<'a>> {
+    tag.children()
+        .top()
+        .iter()
+        .filter_map(|handle| handle.get(parser))
+        .collect()
+}
+
+struct Renderer<'a> {
+    template: &'a Template,
+    scope: &'a Scope<'a>,
+    slot: Option<&'a Slot<'a>>,
+}
+
+/// The children passed to a component, they're rendered in place of its `<slot>`
+/// in the scope of the caller.
+struct Slot<'a> {
+    nodes: &'a [Node],
+    caller: &'a Renderer<'a>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Renders a list of sibling nodes, control flow tags are expanded in place.
+    fn render_all(
+        &self,
+        nodes: &[Node],
+        inherited: &HashMap<String, String>,
+        out: &mut Vec<Elem>,
+    ) -> Result<()> {
+        let mut nodes = nodes.iter().peekable();
+        while let Some(node) = nodes.next() {
+            match node {
+                Node::Tag(tag) if tag.name == "if" => {
+                    let alternative = match nodes.peek().copied() {
+                        Some(Node::Tag(next)) if next.name == "else" => {
+                            nodes.next();
+                            Some(next)
+                        }
+                        _ => None,
+                    };
+                    let cond = tag
+                        .attr("cond")
+                        .and_then(|cond| {
+                            binding::evaluate(cond, self.scope)
+                                .map_err(|kind| Error::new(kind).with_attribute("cond"))
+                        })
+                        .map_err(|err| err.or_position(tag.position))?;
+                    if cond {
+                        self.render_all(&tag.children, inherited, out)?;
+                    } else if let Some(alternative) = alternative {
+                        self.render_all(&alternative.children, inherited, out)?;
+                    }
                 }
-                b"img" => {
-                    let atlas: Option<Cow<str>> = attrs.read_attr("atlas").ok();
-
-                    if let Some(atlas) = atlas {
-                        let part: Option<Cow<str>> = attrs.read_attr("part").ok();
-                        let color: Option<Color> = attrs.read_attr("tint").ok();
-                        let nine_slice = attrs.read_attr("nine-slice").unwrap_or(false);
-
-                        let elem = Elem::new_image(&atlas, part.as_deref(), color, nine_slice)
-                            .with_layout(parse_layout(attrs)?);
-                        Ok(Some(elem))
-                    } else {
-                        Ok(None)
+                Node::Tag(tag) if tag.name == "slot" => match self.slot {
+                    Some(slot) if !slot.nodes.is_empty() => {
+                        slot.caller
+                            .render_all(slot.nodes, inherited, out)
+                            .map_err(|err| slot.caller.template.locate(err))?;
                     }
+                    _ => self.render_all(&tag.children, inherited, out)?,
+                },
+                Node::Tag(tag) if tag.is_include() => {
+                    self.render_include(tag, inherited, out)
+                        .map_err(|err| err.or_position(tag.position))?;
+                }
+                Node::Tag(tag) if tag.name == "for" => {
+                    self.render_loop(tag, inherited, out)
+                        .map_err(|err| err.or_position(tag.position))?;
                 }
-                b"text" => {
-                    let attrs = tag.attributes();
-                    let text = tag.inner_text(parser);
-                    let font_size: Option<i32> = attrs.read_attr("font-size").ok();
-                    let color: Option<Color> = attrs.read_attr("color").ok();
+                node => {
+                    if let Some(elem) = self.render(node, inherited)? {
+                        out.push(elem);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 
-                    let elem = Elem::new_text(&text, font_size, color).with_layout(parse_layout(attrs)?);
+    /// Renders the children of the tag once for every item of the list,
+    /// the item is bound to the variable named by `each="item in items"`.
+    fn render_loop(&self, tag: &Tag, inherited: &HashMap<String, String>, out: &mut Vec<Elem>) -> Result<()> {
+        let each = tag.attr("each")?;
+        let invalid = |kind: ErrorKind| Error::new(kind).with_attribute("each");
+        let (name, path) = each
+            .split_once(" in ")
+            .map(|(name, path)| (name.trim(), path.trim()))
+            .filter(|(name, path)| !name.is_empty() && !path.is_empty())
+            .ok_or_else(|| invalid(ErrorKind::InvalidValue(each.to_owned())))?;
+        let items = match self.scope.lookup(path).map_err(invalid)? {
+            Value::List(items) => items,
+            _ => return Err(invalid(ErrorKind::TypeMismatch(path.to_owned(), "list"))),
+        };
+
+        for item in items {
+            let scope = Scope::Local {
+                name,
+                value: item,
+                parent: self.scope,
+            };
+            let renderer = Renderer {
+                template: self.template,
+                scope: &scope,
+                slot: self.slot,
+            };
+            renderer.render_all(&tag.children, inherited, out)?;
+        }
+        Ok(())
+    }
+
+    /// Renders an included template, the attributes of the tag are passed to it as its bindings.
+    fn render_include(
+        &self,
+        tag: &Tag,
+        inherited: &HashMap<String, String>,
+        out: &mut Vec<Elem>,
+    ) -> Result<()> {
+        let name = tag.include_name()?;
+        let template = self
+            .template
+            .includes
+            .get(&*name)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedTag(tag.name.clone())))?;
+
+        let mut params = Bindings::default();
+        for (key, val) in &tag.attrs {
+            if tag.name == "include" && key == "src" {
+                continue;
+            }
+            let val = binding::bind(val, self.scope).map_err(|kind| Error::new(kind).with_attribute(key))?;
+            params.set_value(key, val);
+        }
+        let scope = if tag.name == "include" {
+            Scope::Layer {
+                bindings: &params,
+                parent: self.scope,
+            }
+        } else {
+            Scope::Root(&params)
+        };
+        let slot = Slot {
+            nodes: &tag.children,
+            caller: self,
+        };
+        let renderer = Renderer {
+            template,
+            scope: &scope,
+            slot: Some(&slot),
+        };
+        renderer
+            .render_all(std::slice::from_ref(&template.root), inherited, out)
+            .map_err(|err| template.locate(err))
+    }
+
+    fn render(&self, node: &Node, inherited: &HashMap<String, String>) -> Result<Option<Elem>> {
+        match node {
+            Node::Tag(tag) => self
+                .render_tag(tag, inherited)
+                .map_err(|err| err.or_position(tag.position)),
+            Node::Text(text) => {
+                let props = Props {
+                    values: inherited.clone(),
+                };
+                let elem = Elem::new(ElemKind::Text {
+                    text: binding::interpolate(text, self.scope)
+                        .map_err(Error::new)?
+                        .into_owned(),
+                    font_size: props.read_attr("font-size")?,
+                    color: props.read_attr("color")?,
+                });
+                Ok(Some(elem))
+            }
+        }
+    }
+
+    fn render_tag(&self, tag: &Tag, inherited: &HashMap<String, String>) -> Result<Option<Elem>> {
+        let props = Props::resolve(tag, &self.template.style, self.scope, inherited)?;
+        match tag.name.as_str() {
+            "box" => {
+                let mut children = vec![];
+                self.render_all(&tag.children, &props.inherited(), &mut children)?;
+                let color: Option<Color> = props.read_attr("background-color")?;
+                let border_color: Option<Color> = props.read_attr("border-color")?;
+                let border_radius: Option<f32> = props.read_attr("border-radius")?;
+
+                let elem = Elem::new(ElemKind::Box {
+                    background_color: color,
+                    border_color,
+                    border_radius,
+                })
+                .with_children(children)
+                .with_layout(parse_layout(&props)?)
+                .with_id(props.read_attr::<Cow<str>>("id")?.map(Cow::into_owned))
+                .with_appearance(parse_appearance(&props)?)
+                .with_handlers(parse_handlers(&props)?);
+                Ok(Some(elem))
+            }
+            "img" => {
+                let atlas: Option<Cow<str>> = props.read_attr("atlas")?;
+
+                if let Some(atlas) = atlas {
+                    let part: Option<Cow<str>> = props.read_attr("part")?;
+                    let color: Option<Color> = props.read_attr("tint")?;
+                    let nine_slice = props.read_attr("nine-slice")?.unwrap_or(false);
+
+                    let elem = Elem::new(ElemKind::Image {
+                        atlas: atlas.into_owned(),
+                        part: part.map(Cow::into_owned),
+                        tint: color,
+                        nine_slice,
+                    })
+                    .with_layout(parse_layout(&props)?)
+                    .with_id(props.read_attr::<Cow<str>>("id")?.map(Cow::into_owned))
+                    .with_appearance(parse_appearance(&props)?)
+                    .with_handlers(parse_handlers(&props)?);
                     Ok(Some(elem))
+                } else {
+                    Ok(None)
                 }
-                _ => Err(anyhow!("Unexpected tag")),
+            }
+            "else" => Err(Error::new(ErrorKind::UnexpectedTag("else".to_owned()))),
+            "text" => {
+                let mut text = String::new();
+                tag.text_content(&mut text);
+                let font_size: Option<i32> = props.read_attr("font-size")?;
+                let color: Option<Color> = props.read_attr("color")?;
+
+                let elem = Elem::new(ElemKind::Text {
+                    text: binding::interpolate(&text, self.scope)
+                        .map_err(Error::new)?
+                        .into_owned(),
+                    font_size,
+                    color,
+                })
+                .with_layout(parse_layout(&props)?)
+                .with_id(props.read_attr::<Cow<str>>("id")?.map(Cow::into_owned))
+                .with_appearance(parse_appearance(&props)?)
+                .with_handlers(parse_handlers(&props)?);
+                Ok(Some(elem))
+            }
+            other => {
+                let class = custom_tag_class(other)
+                    .ok_or_else(|| Error::new(ErrorKind::UnexpectedTag(other.to_owned())))?;
+                if !tag.children.is_empty() {
+                    return Err(Error::new(ErrorKind::UnexpectedChildren(other.to_owned())));
+                }
+                let mut attributes: Vec<_> = props
+                    .values
+                    .iter()
+                    .map(|(name, val)| (name.clone(), val.clone()))
+                    .collect();
+                attributes.sort();
+
+                let elem = Elem::new(ElemKind::Custom { class, attributes })
+                    .with_layout(parse_layout(&props)?)
+                    .with_id(props.read_attr::<Cow<str>>("id")?.map(Cow::into_owned))
+                    .with_appearance(parse_appearance(&props)?)
+                    .with_handlers(parse_handlers(&props)?);
+                Ok(Some(elem))
             }
         }
-        tl::Node::Raw(bytes) => {
-            let elem = Elem::new_text(&bytes.as_utf8_str(), None, None);
-            Ok(Some(elem))
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub attribute: Option<String>,
+    pub position: Option<Position>,
+    pub template: Option<String>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            attribute: None,
+            position: None,
+            template: None,
         }
-        tl::Node::Comment(_) => Ok(None),
+    }
+
+    pub fn with_attribute(mut self, name: &str) -> Self {
+        self.attribute = Some(name.to_owned());
+        self
+    }
+
+    /// Attaches the position of the tag unless the error has already been located,
+    /// errors raised by nested tags keep their own position.
+    fn or_position(mut self, position: Option<Position>) -> Self {
+        if self.position.is_none() {
+            self.position = position;
+        }
+        self
+    }
+
+    /// Attaches the name of the template unless the error comes from one of its includes.
+    fn or_template(mut self, name: &str) -> Self {
+        if self.template.is_none() {
+            self.template = Some(name.to_owned());
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(template) = &self.template {
+            write!(f, "{}:", template)?;
+            if self.position.is_none() {
+                write!(f, " ")?;
+            }
+        }
+        if let Some(pos) = self.position {
+            write!(f, "{}: ", pos)?;
+        }
+        if let Some(attr) = &self.attribute {
+            write!(f, "attribute '{}': ", attr)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    Io(std::io::Error),
+    Syntax(String),
+    EmptyDocument,
+    UnexpectedTag(String),
+    InvalidValue(String),
+    InvalidColor(String),
+    InvalidDimension(String),
+    InvalidStyle(String),
+    UnboundVariable(String),
+    TypeMismatch(String, &'static str),
+    MissingAttribute(String),
+    IncludeCycle(String),
+    ReservedTag(String),
+    UnexpectedChildren(String),
+    InvalidTemplateName(String),
+    UnknownNamespace(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(err) => write!(f, "could not read the template: {}", err),
+            ErrorKind::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            ErrorKind::EmptyDocument => write!(f, "the template has no root element"),
+            ErrorKind::UnexpectedTag(name) => write!(f, "unexpected tag <{}>", name),
+            ErrorKind::InvalidValue(val) => write!(f, "invalid value '{}'", val),
+            ErrorKind::InvalidColor(val) => write!(f, "invalid color literal '{}'", val),
+            ErrorKind::InvalidDimension(val) => write!(f, "invalid dimension literal '{}'", val),
+            ErrorKind::InvalidStyle(msg) => write!(f, "invalid stylesheet: {}", msg),
+            ErrorKind::UnboundVariable(name) => write!(f, "unbound variable '{}'", name),
+            ErrorKind::TypeMismatch(name, expected) => write!(f, "variable '{}' is not a {}", name, expected),
+            ErrorKind::MissingAttribute(name) => write!(f, "missing attribute '{}'", name),
+            ErrorKind::IncludeCycle(cycle) => write!(f, "include cycle {}", cycle),
+            ErrorKind::ReservedTag(name) => write!(f, "<{}> can't be registered as a custom tag", name),
+            ErrorKind::UnexpectedChildren(name) => write!(f, "<{}> does not accept children", name),
+            ErrorKind::InvalidTemplateName(name) => write!(f, "invalid template name '{}'", name),
+            ErrorKind::UnknownNamespace(name) => write!(f, "no template root registered for '{}'", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn of_tag(source: &str, tag: &tl::HTMLTag) -> Option<Self> {
+        // the tag name is borrowed from the source, the opening bracket precedes it
+        let name = tag.name().as_bytes_borrowed()?;
+        let offset = (name.as_ptr() as usize)
+            .checked_sub(source.as_ptr() as usize)?
+            .checked_sub(1)?;
+        Self::of_offset(source, offset)
+    }
+
+    fn of_offset(source: &str, offset: usize) -> Option<Self> {
+        let preceding = source.get(..offset)?;
+        let line = preceding.matches('\n').count() + 1;
+        let line_start = preceding.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        let column = preceding[line_start..].chars().count() + 1;
+        Some(Self { line, column })
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
 
 pub trait AttrRead<'a>: Sized {
-    fn read(attrs: &'a tl::Attributes, name: &'a str) -> Result<Self>;
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind>;
 }
 
 impl<'a> AttrRead<'a> for Cow<'a, str> {
-    fn read(attrs: &'a tl::Attributes, name: &'a str) -> Result<Self> {
-        let bytes = attrs
-            .get(name)
-            .flatten()
-            .ok_or_else(|| anyhow!("Attribute {} not found", name))?;
-        let str = bytes
-            .as_bytes_borrowed()
-            .and_then(|buf| std::str::from_utf8(buf).ok())
-            .map(Cow::Borrowed)
-            .unwrap_or_else(|| Cow::Owned(bytes.as_utf8_str().into_owned()));
+    #[inline]
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
         Ok(str)
     }
 }
 
 impl<'a> AttrRead<'a> for bool {
     #[inline]
-    fn read(attrs: &'a tl::Attributes, name: &'a str) -> Result<Self> {
-        Ok(attrs.read_attr::<Cow<'a, str>>(name)?.parse()?)
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        str.trim()
+            .parse()
+            .map_err(|_| ErrorKind::InvalidValue(str.into_owned()))
     }
 }
 
 impl<'a> AttrRead<'a> for i32 {
     #[inline]
-    fn read(attrs: &'a tl::Attributes, name: &'a str) -> Result<Self> {
-        Ok(attrs.read_attr::<Cow<'a, str>>(name)?.parse()?)
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        str.trim()
+            .parse()
+            .map_err(|_| ErrorKind::InvalidValue(str.into_owned()))
     }
 }
 
-impl<'a> AttrRead<'a> for Color {
-    fn read(attrs: &'a tl::Attributes, name: &'a str) -> Result<Self> {
-        let str = attrs.read_attr::<Cow<'a, str>>(name)?;
-        let str = str
-            .strip_prefix('#')
-            .ok_or_else(|| anyhow!("Invalid color literal"))?;
-        if str.len() != 6 {
-            return Err(anyhow!("Only full hex color literals allowed"));
-        }
-        let red = u8::from_str_radix(&str[0..2], 16)?;
-        let green = u8::from_str_radix(&str[2..4], 16)?;
-        let blue = u8::from_str_radix(&str[4..6], 16)?;
-        Ok(Color::new(red, green, blue, 255))
+impl<'a> AttrRead<'a> for f32 {
+    #[inline]
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        str.trim()
+            .parse()
+            .map_err(|_| ErrorKind::InvalidValue(str.into_owned()))
     }
 }
 
-pub trait AttrReadSynax<'a> {
-    fn read_attr<A: AttrRead<'a>>(&'a self, name: &'a str) -> Result<A>;
+impl<'a> AttrRead<'a> for Dimension {
+    #[inline]
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        layout::parse_dimension(str.trim()).map_err(|_| ErrorKind::InvalidDimension(str.into_owned()))
+    }
 }
 
-impl<'a> AttrReadSynax<'a> for tl::Attributes<'a> {
-    #[inline]
-    fn read_attr<A: AttrRead<'a>>(&'a self, name: &'a str) -> Result<A> {
-        A::read(self, name)
+/// Reads CSS-like shorthand with one to four values,
+/// in the order of `top right bottom left` with the missing sides mirrored.
+impl<'a> AttrRead<'a> for Edges {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        let vals = str
+            .split_whitespace()
+            .map(|val| val.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ErrorKind::InvalidValue(str.clone().into_owned()))?;
+        let (top, right, bottom, left) = match vals[..] {
+            [all] => (all, all, all, all),
+            [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+            [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+            [top, right, bottom, left] => (top, right, bottom, left),
+            _ => return Err(ErrorKind::InvalidValue(str.into_owned())),
+        };
+        Ok(Edges::new(top, right, bottom, left))
+    }
+}
+
+/// The `gap` shorthand, `row column` or a single value for both.
+struct Gap(f32, f32);
+
+impl<'a> AttrRead<'a> for Gap {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        let vals = str
+            .split_whitespace()
+            .map(|val| val.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ErrorKind::InvalidValue(str.clone().into_owned()))?;
+        match vals[..] {
+            [both] => Ok(Gap(both, both)),
+            [row, column] => Ok(Gap(row, column)),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
+        }
     }
 }
 
-fn parse_layout(attrs: &tl::Attributes) -> Result<Layout> {
-    fn parse_position_type(bytes: &str) -> Result<PositionType> {
-        match bytes {
+impl<'a> AttrRead<'a> for Color {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        color::parse(&str).ok_or_else(|| ErrorKind::InvalidColor(str.into_owned()))
+    }
+}
+
+impl<'a> AttrRead<'a> for PositionType {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
             "relative" => Ok(PositionType::Relative),
             "absolute" => Ok(PositionType::Absolute),
-            _ => return Err(anyhow!("Invalid FlexWrap")),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
+        }
+    }
+}
+
+impl<'a> AttrRead<'a> for Display {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
+            "flex" => Ok(Display::Flex),
+            "none" => Ok(Display::None),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
         }
     }
+}
 
-    fn parse_flex_wrap(bytes: &str) -> Result<FlexWrap> {
-        match bytes {
+impl<'a> AttrRead<'a> for Overflow {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
+            "visible" => Ok(Overflow::Visible),
+            "hidden" => Ok(Overflow::Hidden),
+            "scroll" => Ok(Overflow::Scroll),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
+        }
+    }
+}
+
+impl<'a> AttrRead<'a> for FlexWrap {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
             "no-wrap" => Ok(FlexWrap::NoWrap),
             "wrap" => Ok(FlexWrap::Wrap),
             "wrap-reverse" => Ok(FlexWrap::WrapReverse),
-            _ => return Err(anyhow!("Invalid FlexWrap")),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
         }
     }
+}
 
-    fn parse_flex_dir(bytes: &str) -> Result<FlexDirection> {
-        match bytes {
+impl<'a> AttrRead<'a> for FlexDirection {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
             "row" => Ok(FlexDirection::Row),
             "column" => Ok(FlexDirection::Column),
             "row-reverse" => Ok(FlexDirection::RowReverse),
             "column-reverse" => Ok(FlexDirection::ColumnReverse),
-            _ => return Err(anyhow!("Invalid FlexDirection")),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
         }
     }
+}
 
-    fn parse_flex_align(bytes: &str) -> Result<FlexAlign> {
-        match bytes {
+impl<'a> AttrRead<'a> for FlexAlign {
+    fn read(str: Cow<'a, str>) -> Result<Self, ErrorKind> {
+        match str.as_ref() {
             "inherit" => Ok(FlexAlign::Inherit),
             "stretch" => Ok(FlexAlign::Stretch),
             "start" => Ok(FlexAlign::Start),
@@ -175,52 +872,253 @@ fn parse_layout(attrs: &tl::Attributes) -> Result<Layout> {
             "space-between" => Ok(FlexAlign::SpaceBetween),
             "space-around" => Ok(FlexAlign::SpaceAround),
             "baseline" => Ok(FlexAlign::Baseline),
-            _ => return Err(anyhow!("Invalid FlexAlign")),
+            _ => Err(ErrorKind::InvalidValue(str.into_owned())),
         }
     }
+}
 
-    let mut layout = Layout::new();
-    if let Some(bytes) = attrs.get("position").flatten() {
-        layout.with_position_type(parse_position_type(&bytes.as_utf8_str())?);
+pub trait AttrReadSynax<'a> {
+    fn read_attr<A: AttrRead<'a>>(&'a self, name: &'a str) -> Result<Option<A>>;
+}
+
+/// The properties of an element, its attributes take precedence over the declarations
+/// of the matching style rules and the values inherited from the parent.
+struct Props {
+    values: HashMap<String, String>,
+}
+
+impl Props {
+    fn resolve(
+        tag: &Tag,
+        sheet: &Stylesheet,
+        scope: &Scope,
+        inherited: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let attrs = tag
+            .attrs
+            .iter()
+            .map(|(name, val)| {
+                let val =
+                    binding::interpolate(val, scope).map_err(|kind| Error::new(kind).with_attribute(name))?;
+                Ok((name.as_str(), val))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let id = attrs
+            .iter()
+            .find(|(name, _)| *name == "id")
+            .map(|(_, val)| &val[..]);
+        let class = attrs
+            .iter()
+            .find(|(name, _)| *name == "class")
+            .map(|(_, val)| &val[..]);
+        let classes: Vec<&str> = class.unwrap_or_default().split_whitespace().collect();
+
+        let mut values = sheet.resolve(inherited, &tag.name, id, &classes);
+        // shorthands go first so that they don't reset the longhands given alongside them
+        let (shorthands, longhands): (Vec<_>, Vec<_>) = attrs
+            .iter()
+            .partition(|(name, _)| style::SHORTHANDS.iter().any(|(shorthand, _)| shorthand == name));
+        for (name, val) in shorthands.into_iter().chain(longhands) {
+            style::set_property(&mut values, name, val);
+        }
+        Ok(Self { values })
+    }
+
+    fn inherited(&self) -> HashMap<String, String> {
+        style::INHERITED
+            .iter()
+            .filter_map(|name| Some((name.to_string(), self.values.get(*name)?.clone())))
+            .collect()
+    }
+}
+
+impl<'a> AttrReadSynax<'a> for Props {
+    fn read_attr<A: AttrRead<'a>>(&'a self, name: &'a str) -> Result<Option<A>> {
+        match self.values.get(name) {
+            Some(val) => A::read(Cow::Borrowed(val))
+                .map(Some)
+                .map_err(|kind| Error::new(kind).with_attribute(name)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_layout<'a, P: AttrReadSynax<'a>>(attrs: &'a P) -> Result<Layout> {
+    let mut layout = Layout::default();
+    if let Some(val) = attrs.read_attr("position")? {
+        layout.position_type = val;
+    }
+    if let Some(val) = attrs.read_attr("flex-wrap")? {
+        layout.flex_wrap = val;
+    }
+    if let Some(val) = attrs.read_attr("flex-direction")? {
+        layout.flex_direction = val;
+    }
+    if let Some(val) = attrs.read_attr("align-items")? {
+        layout.align_items = val;
+    }
+    if let Some(val) = attrs.read_attr("align-content")? {
+        layout.align_content = val;
+    }
+    if let Some(val) = attrs.read_attr("justify-content")? {
+        layout.justify_content = val;
     }
-    if let Some(bytes) = attrs.get("flex-wrap").flatten() {
-        layout.with_flex_wrap(parse_flex_wrap(&bytes.as_utf8_str())?);
+    if let Some(val) = attrs.read_attr("align-self")? {
+        layout.align_self = val;
     }
-    if let Some(bytes) = attrs.get("flex-direction").flatten() {
-        layout.with_flex_direction(parse_flex_dir(&bytes.as_utf8_str())?);
+    if let Some(val) = attrs.read_attr("width")? {
+        layout.width = val;
     }
-    if let Some(bytes) = attrs.get("align-items").flatten() {
-        layout.with_align_items(parse_flex_align(&bytes.as_utf8_str())?);
+    if let Some(val) = attrs.read_attr("height")? {
+        layout.height = val;
     }
-    if let Some(bytes) = attrs.get("align-content").flatten() {
-        layout.with_align_content(parse_flex_align(&bytes.as_utf8_str())?);
+    if let Some(val) = attrs.read_attr("min-width")? {
+        layout.min_width = val;
     }
-    if let Some(bytes) = attrs.get("justify-content").flatten() {
-        layout.with_justify_content(parse_flex_align(&bytes.as_utf8_str())?);
+    if let Some(val) = attrs.read_attr("max-width")? {
+        layout.max_width = val;
     }
-    if let Some(bytes) = attrs.get("width").flatten() {
-        layout.with_width(&bytes.as_utf8_str());
+    if let Some(val) = attrs.read_attr("min-height")? {
+        layout.min_height = val;
     }
-    if let Some(bytes) = attrs.get("height").flatten() {
-        layout.with_height(&bytes.as_utf8_str());
+    if let Some(val) = attrs.read_attr("max-height")? {
+        layout.max_height = val;
     }
-    if let Some(bytes) = attrs.get("padding").flatten() {
-        let val = bytes.as_utf8_str().parse()?;
-        layout.with_padding(val);
+    if let Some(val) = attrs.read_attr("left")? {
+        layout.left = val;
     }
-    if let Some(bytes) = attrs.get("margin").flatten() {
-        let val = bytes.as_utf8_str().parse()?;
-        layout.with_margin(val);
+    if let Some(val) = attrs.read_attr("right")? {
+        layout.right = val;
     }
-    if let Some(bytes) = attrs.get("flex-grow").flatten() {
-        let val = bytes.as_utf8_str().parse()?;
-        layout.with_flex_grow(val);
+    if let Some(val) = attrs.read_attr("top")? {
+        layout.top = val;
+    }
+    if let Some(val) = attrs.read_attr("bottom")? {
+        layout.bottom = val;
+    }
+    if let Some(val) = attrs.read_attr("padding")? {
+        layout.padding = val;
+    }
+    if let Some(val) = attrs.read_attr("padding-left")? {
+        layout.padding.left = val;
+    }
+    if let Some(val) = attrs.read_attr("padding-right")? {
+        layout.padding.right = val;
+    }
+    if let Some(val) = attrs.read_attr("padding-top")? {
+        layout.padding.top = val;
+    }
+    if let Some(val) = attrs.read_attr("padding-bottom")? {
+        layout.padding.bottom = val;
+    }
+    if let Some(val) = attrs.read_attr("margin")? {
+        layout.margin = val;
+    }
+    if let Some(val) = attrs.read_attr("margin-left")? {
+        layout.margin.left = val;
+    }
+    if let Some(val) = attrs.read_attr("margin-right")? {
+        layout.margin.right = val;
+    }
+    if let Some(val) = attrs.read_attr("margin-top")? {
+        layout.margin.top = val;
+    }
+    if let Some(val) = attrs.read_attr("margin-bottom")? {
+        layout.margin.bottom = val;
+    }
+    if let Some(val) = attrs.read_attr("border-width")? {
+        layout.border = val;
+    }
+    if let Some(val) = attrs.read_attr("border-left-width")? {
+        layout.border.left = val;
+    }
+    if let Some(val) = attrs.read_attr("border-right-width")? {
+        layout.border.right = val;
+    }
+    if let Some(val) = attrs.read_attr("border-top-width")? {
+        layout.border.top = val;
+    }
+    if let Some(val) = attrs.read_attr("border-bottom-width")? {
+        layout.border.bottom = val;
+    }
+    if let Some(val) = attrs.read_attr("flex-grow")? {
+        layout.flex_grow = val;
+    }
+    if let Some(val) = attrs.read_attr("flex-shrink")? {
+        layout.flex_shrink = val;
+    }
+    if let Some(val) = attrs.read_attr("flex-basis")? {
+        layout.flex_basis = val;
+    }
+    if let Some(val) = attrs.read_attr("display")? {
+        layout.display = val;
+    }
+    if let Some(val) = attrs.read_attr("overflow")? {
+        layout.overflow = val;
+    }
+    if let Some(Gap(row, column)) = attrs.read_attr("gap")? {
+        layout.row_gap = row;
+        layout.column_gap = column;
+    }
+    if let Some(val) = attrs.read_attr("row-gap")? {
+        layout.row_gap = val;
+    }
+    if let Some(val) = attrs.read_attr("column-gap")? {
+        layout.column_gap = val;
     }
 
     Ok(layout)
 }
 
-mod test {
+fn parse_appearance<'a, P: AttrReadSynax<'a>>(attrs: &'a P) -> Result<Appearance> {
+    let mut appearance = Appearance::default();
+    if let Some(val) = attrs.read_attr::<f32>("opacity")? {
+        appearance.opacity = val.clamp(0f32, 1f32);
+    }
+    if let Some(val) = attrs.read_attr::<Cow<str>>("visibility")? {
+        appearance.visible = match &*val {
+            "visible" => true,
+            "hidden" => false,
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidValue(val.into_owned())).with_attribute("visibility"))
+            }
+        };
+    }
+    Ok(appearance)
+}
+
+/// Reads the event attributes, their values are names of methods of the target object.
+fn parse_handlers<'a, P: AttrReadSynax<'a>>(attrs: &'a P) -> Result<Vec<Handler>> {
+    let mut handlers = vec![];
+    for event in Event::ALL {
+        if let Some(method) = attrs.read_attr::<Cow<str>>(event.attribute())? {
+            let is_valid = method.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+                && method.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+            if !is_valid {
+                let err = Error::new(ErrorKind::InvalidValue(method.into_owned()));
+                return Err(err.with_attribute(event.attribute()));
+            }
+            handlers.push(Handler {
+                event,
+                method: method.into_owned(),
+            });
+        }
+    }
+    Ok(handlers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use flexlayout_rs::Dimension;
+
+    use super::{ErrorKind, Position, Template};
+    use crate::binding::{Bindings, Value};
+    use crate::cache;
+    use crate::style::Stylesheet;
+    use crate::tree::{Color, Edges, ElemKind, Event, Handler};
+
     #[test]
     fn parse_simple_box() -> anyhow::Result<()> {
         let dom = tl::parse("<box flex-grow='1'>as</box>", tl::ParserOptions::default())?;
@@ -230,4 +1128,346 @@ mod test {
         assert_eq!(tag.as_utf8_str(), "box");
         Ok(())
     }
+
+    #[test]
+    fn parse_elem_tree() -> anyhow::Result<()> {
+        let elem = super::parse(
+            "<box flex-grow='2' padding='4' background-color='#ff0000'><text font-size='18'>Hi</text></box>",
+        )?;
+        assert!(matches!(
+            elem.kind,
+            ElemKind::Box { background_color: Some(color), .. } if color == Color::new(255, 0, 0, 255)
+        ));
+        assert_eq!(elem.layout.flex_grow, 2.0);
+        assert_eq!(elem.layout.padding.top, 4.0);
+        assert_eq!(elem.children.len(), 1);
+        assert!(matches!(
+            &elem.children[0].kind,
+            ElemKind::Text { text, font_size: Some(18), .. } if text == "Hi"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_spacing_shorthand() -> anyhow::Result<()> {
+        let elem = super::parse("<box padding='4 8' margin='1 2 3 4' margin-left='10'></box>")?;
+        assert_eq!(elem.layout.padding, Edges::new(4.0, 8.0, 4.0, 8.0));
+        assert_eq!(elem.layout.margin, Edges::new(1.0, 2.0, 3.0, 10.0));
+
+        let elem = super::parse("<box padding='1 2 3' padding-bottom='0'></box>")?;
+        assert_eq!(elem.layout.padding, Edges::new(1.0, 2.0, 0.0, 2.0));
+        assert!(super::parse("<box margin='1 2 3 4 5'></box>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_size_constraints() -> anyhow::Result<()> {
+        let elem =
+            super::parse("<box min-width='10pt' max-width='50%' min-height='5pt' max-height='100pt'></box>")?;
+        assert!(matches!(elem.layout.min_width, Dimension::Point(v) if v == 10.0));
+        assert!(matches!(elem.layout.max_width, Dimension::Percent(v) if v == 50.0));
+        assert!(matches!(elem.layout.min_height, Dimension::Point(v) if v == 5.0));
+        assert!(matches!(elem.layout.max_height, Dimension::Point(v) if v == 100.0));
+        Ok(())
+    }
+
+    #[test]
+    fn report_unexpected_tag_position() {
+        let err = super::parse("<box>\n  <text>a</text>\n  <button/>\n</box>").unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::UnexpectedTag(name) if name == "button"));
+        assert_eq!(err.position, Some(Position { line: 3, column: 3 }));
+    }
+
+    #[test]
+    fn parse_ids() -> anyhow::Result<()> {
+        let style = Stylesheet::parse("#price { font-size: 20 }")?;
+        let template = Template::parse_with_style(
+            "<box id='shop'><text id='price'>100</text><text>€</text></box>",
+            style,
+        )?;
+        let elem = template.render(&Bindings::default())?;
+        assert_eq!(elem.id.as_deref(), Some("shop"));
+        assert_eq!(elem.children[0].id.as_deref(), Some("price"));
+        assert!(matches!(elem.children[0].kind, ElemKind::Text {
+            font_size: Some(20),
+            ..
+        }));
+        assert_eq!(elem.children[1].id, None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_event_handlers() -> anyhow::Result<()> {
+        let elem = super::parse("<box on-click='OnBuy'><text on-hover-enter='OnHover'>Buy</text></box>")?;
+        assert_eq!(elem.handlers, vec![Handler {
+            event: Event::Click,
+            method: "OnBuy".to_owned()
+        }]);
+        assert_eq!(elem.children[0].handlers[0].event, Event::HoverEnter);
+
+        let err = super::parse("<box on-hover-leave='On Leave'></box>").unwrap_err();
+        assert_eq!(err.attribute.as_deref(), Some("on-hover-leave"));
+        Ok(())
+    }
+
+    #[test]
+    fn report_invalid_attribute() {
+        let err = super::parse("<box>\n<box width='12px'></box></box>").unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::InvalidDimension(val) if val == "12px"));
+        assert_eq!(err.attribute.as_deref(), Some("width"));
+        assert_eq!(err.position, Some(Position { line: 2, column: 1 }));
+        assert_eq!(
+            err.to_string(),
+            "2:1: attribute 'width': invalid dimension literal '12px'"
+        );
+    }
+
+    #[test]
+    fn apply_style_rules() -> anyhow::Result<()> {
+        let elem = super::parse(
+            "<style>
+               box { padding: 4 }
+               .panel { width: 50pt; color: #00ff00 }
+               #title { font-size: 30 }
+             </style>
+             <box class='panel wide' padding-left='1'><text id='title'>Hi</text><text font-size='12'>a</text></box>",
+        )?;
+        assert!(matches!(elem.layout.width, Dimension::Point(v) if v == 50.0));
+        assert_eq!(elem.layout.padding, Edges::new(4.0, 4.0, 4.0, 1.0));
+        assert_eq!(elem.children.len(), 2);
+        assert!(matches!(
+            &elem.children[0].kind,
+            ElemKind::Text { font_size: Some(30), color: Some(color), .. } if *color == Color::new(0, 255, 0, 255)
+        ));
+        assert!(matches!(&elem.children[1].kind, ElemKind::Text {
+            font_size: Some(12),
+            ..
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_external_stylesheet() -> anyhow::Result<()> {
+        let style = Stylesheet::parse("box { margin: 2 } .a { margin-top: 5 }")?;
+        let elem = Template::parse_with_style(
+            "<style>.a { margin-left: 3 }</style><box class='a' margin='1'></box>",
+            style,
+        )?
+        .render(&Bindings::default())?;
+        assert_eq!(elem.layout.margin, Edges::new(1.0, 1.0, 1.0, 1.0));
+
+        let style = Stylesheet::parse("box { margin: 2 } .a { margin-top: 5 }")?;
+        let elem = Template::parse_with_style("<box class='a'></box>", style)?.render(&Bindings::default())?;
+        assert_eq!(elem.layout.margin, Edges::new(5.0, 2.0, 2.0, 2.0));
+        Ok(())
+    }
+
+    #[test]
+    fn report_invalid_style() {
+        let err = super::parse("<style>box { width: 12px }</style><box></box>").unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::InvalidDimension(val) if val == "12px"));
+        assert!(super::parse("<style>box text { width: 1pt }</style><box></box>").is_err());
+    }
+
+    #[test]
+    fn render_with_bindings() -> anyhow::Result<()> {
+        let template = Template::parse(
+            "<box width='{{ hpPct }}%' class='{{ kind }}'><text>{{ ammo }} / {{max}}</text></box>",
+        )?;
+
+        let bindings = Bindings::default()
+            .with("hpPct", "50")
+            .with("kind", "panel")
+            .with("ammo", "12")
+            .with("max", "30");
+        let elem = template.render(&bindings)?;
+        assert!(matches!(elem.layout.width, Dimension::Percent(v) if v == 50.0));
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "12 / 30"));
+
+        let bindings = bindings.with("hpPct", "25").with("ammo", "0");
+        let elem = template.render(&bindings)?;
+        assert!(matches!(elem.layout.width, Dimension::Percent(v) if v == 25.0));
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "0 / 30"));
+        Ok(())
+    }
+
+    #[test]
+    fn report_unbound_variable() -> anyhow::Result<()> {
+        let template = Template::parse("<box>\n<box height='{{ size }}pt'></box></box>")?;
+        let err = template.render(&Bindings::default()).unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::UnboundVariable(name) if name == "size"));
+        assert_eq!(err.attribute.as_deref(), Some("height"));
+        assert_eq!(err.position, Some(Position { line: 2, column: 1 }));
+        Ok(())
+    }
+
+    #[test]
+    fn render_conditionals() -> anyhow::Result<()> {
+        let template = Template::parse(
+            "<box>\
+               <if cond='hasQuest'><text>{{ quest }}</text></if>\
+               <else><text>No quest</text></else>\
+               <if cond='ammo < 5'><text>Low ammo</text></if>\
+             </box>",
+        )?;
+
+        let bindings = Bindings::default()
+            .with("hasQuest", "true")
+            .with("quest", "The Heist")
+            .with("ammo", "3");
+        let elem = template.render(&bindings)?;
+        assert_eq!(elem.children.len(), 2);
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "The Heist"));
+        assert!(matches!(&elem.children[1].kind, ElemKind::Text { text, .. } if text == "Low ammo"));
+
+        let bindings = bindings.with("hasQuest", "false").with("ammo", "30");
+        let elem = template.render(&bindings)?;
+        assert_eq!(elem.children.len(), 1);
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "No quest"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_loops() -> anyhow::Result<()> {
+        let template = Template::parse(
+            "<box flex-direction='column'>\
+               <for each='row in rows'>\
+                 <box><text>{{ row.name }}</text><if cond='row.count > 1'><text>x{{ row.count }}</text></if></box>\
+               </for>\
+             </box>",
+        )?;
+        let row =
+            |name: &str, count: &str| Value::Map(Bindings::default().with("name", name).with("count", count));
+        let bindings = Bindings::default()
+            .with_value("rows", Value::List(vec![row("Medkit", "3"), row("Grenade", "1")]));
+
+        let elem = template.render(&bindings)?;
+        assert_eq!(elem.children.len(), 2);
+        assert_eq!(elem.children[0].children.len(), 2);
+        assert_eq!(elem.children[1].children.len(), 1);
+        assert!(matches!(&elem.children[0].children[1].kind, ElemKind::Text { text, .. } if text == "x3"));
+        assert!(
+            matches!(&elem.children[1].children[0].kind, ElemKind::Text { text, .. } if text == "Grenade")
+        );
+
+        let err = template
+            .render(&Bindings::default().with("rows", "none"))
+            .unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::TypeMismatch(name, "list") if name == "rows"));
+        assert_eq!(err.attribute.as_deref(), Some("each"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_components() -> anyhow::Result<()> {
+        let source: HashMap<&str, &str> = [
+            (
+                "page",
+                "<box><stat-bar label='HP' value='{{ hp }}'><text>!</text></stat-bar><include src='widgets/footer' label='Armor'/></box>",
+            ),
+            (
+                "components/stat-bar",
+                "<box><text>{{ label }}: {{ value }}</text><slot><text>-</text></slot></box>",
+            ),
+            ("widgets/footer", "<stat-bar label='{{ label }}' value='{{ armor }}'/>"),
+        ]
+        .into_iter()
+        .collect();
+
+        let template = Template::load_from(&source, "page")?;
+        let err = template
+            .render(&Bindings::default().with("hp", "80"))
+            .unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::UnboundVariable(name) if name == "armor"));
+        assert_eq!(err.template.as_deref(), Some("widgets/footer"));
+
+        let elem = template.render(&Bindings::default().with("hp", "80").with("armor", "40"))?;
+        assert_eq!(elem.children.len(), 2);
+        let children = &elem.children[0].children;
+        assert!(matches!(&children[0].kind, ElemKind::Text { text, .. } if text == "HP: 80"));
+        assert!(matches!(&children[1].kind, ElemKind::Text { text, .. } if text == "!"));
+        let children = &elem.children[1].children;
+        assert!(matches!(&children[0].kind, ElemKind::Text { text, .. } if text == "Armor: 40"));
+        assert!(matches!(&children[1].kind, ElemKind::Text { text, .. } if text == "-"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_custom_tags() -> anyhow::Result<()> {
+        // the registry is shared by all tests, the tag is removed again even if the test fails
+        struct Unregister(&'static str);
+        impl Drop for Unregister {
+            fn drop(&mut self) {
+                super::CUSTOM_TAGS.lock().unwrap().remove(self.0);
+            }
+        }
+        let _unregister = Unregister("gauge");
+        super::register_tag("gauge", "MyMod.Gauge")?;
+        let elem = super::parse("<box color='#00ff00'><gauge value='80' width='20pt'/></box>")?;
+        let gauge = &elem.children[0];
+        assert!(matches!(
+            &gauge.kind,
+            ElemKind::Custom { class, attributes } if class == "MyMod.Gauge" && attributes == &[
+                ("color".to_owned(), "#00ff00".to_owned()),
+                ("value".to_owned(), "80".to_owned()),
+                ("width".to_owned(), "20pt".to_owned()),
+            ]
+        ));
+        assert!(matches!(gauge.layout.width, Dimension::Point(v) if v == 20.0));
+
+        let err = super::parse("<gauge><text>a</text></gauge>").unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::UnexpectedChildren(name) if name == "gauge"));
+        assert!(super::register_tag("box", "MyMod.Box").is_err());
+        assert!(super::register_tag("my tag", "MyMod.Box").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_namespaced_includes() -> anyhow::Result<()> {
+        let source: HashMap<&str, &str> = [
+            (
+                "mymod:hud",
+                "<box><include src='label'/><include src='base:label'/></box>",
+            ),
+            ("mymod:label", "<text>Mod</text>"),
+            ("base:label", "<text>Base</text>"),
+        ]
+        .into_iter()
+        .collect();
+
+        let elem = Template::load_from(&source, "mymod:hud")?.render(&Bindings::default())?;
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "Mod"));
+        assert!(matches!(&elem.children[1].kind, ElemKind::Text { text, .. } if text == "Base"));
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_inline_includes_from_registered_roots() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("flexy-inline-includes-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("hud"))?;
+        std::fs::write(root.join("hud").join("label.html"), "<text>Mod</text>")?;
+        cache::add_root("mymod", root.to_str().unwrap())?;
+
+        let res = super::parse("<box><include src='mymod:hud/label'/></box>");
+        std::fs::remove_dir_all(&root)?;
+        let elem = res?;
+        assert!(matches!(&elem.children[0].kind, ElemKind::Text { text, .. } if text == "Mod"));
+        Ok(())
+    }
+
+    #[test]
+    fn report_include_cycle() {
+        let source: HashMap<&str, &str> = [
+            ("a", "<box><include src='b'/></box>"),
+            ("b", "<box>\n<include src='a'/></box>"),
+        ]
+        .into_iter()
+        .collect();
+
+        let err = Template::load_from(&source, "a").unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::IncludeCycle(cycle) if cycle == "a -> b -> a"));
+        assert_eq!(err.template.as_deref(), Some("b"));
+        assert_eq!(err.position, Some(Position { line: 2, column: 1 }));
+        assert_eq!(err.to_string(), "b:2:1: include cycle a -> b -> a");
+    }
 }
@@ -0,0 +1,128 @@
+pub const DEFAULT_FONT_FAMILY: &str = "base\\gameplay\\gui\\fonts\\raj\\raj.inkfontfamily";
+pub const DEFAULT_FONT_SIZE: i32 = 24;
+
+/// Approximate glyph metrics of a font family, expressed as fractions of the font size.
+#[derive(Debug)]
+pub struct FontMetrics {
+    pub family: &'static str,
+    pub line_height: f32,
+    pub space: f32,
+    pub narrow: f32,
+    pub digit: f32,
+    pub lowercase: f32,
+    pub uppercase: f32,
+    pub wide: f32,
+}
+
+static FONTS: &[FontMetrics] = &[
+    FontMetrics {
+        family: DEFAULT_FONT_FAMILY,
+        line_height: 1.2,
+        space: 0.22,
+        narrow: 0.22,
+        digit: 0.46,
+        lowercase: 0.44,
+        uppercase: 0.52,
+        wide: 0.74,
+    },
+    FontMetrics {
+        family: "base\\gameplay\\gui\\fonts\\orbitron\\orbitron.inkfontfamily",
+        line_height: 1.25,
+        space: 0.3,
+        narrow: 0.28,
+        digit: 0.66,
+        lowercase: 0.62,
+        uppercase: 0.72,
+        wide: 0.94,
+    },
+];
+
+impl FontMetrics {
+    pub fn for_family(family: &str) -> &'static FontMetrics {
+        FONTS
+            .iter()
+            .find(|font| font.family == family)
+            .unwrap_or(&FONTS[0])
+    }
+
+    pub fn advance(&self, ch: char) -> f32 {
+        match ch {
+            ' ' | '\t' => self.space,
+            'i' | 'j' | 'l' | 'I' | '.' | ',' | ':' | ';' | '\'' | '"' | '!' | '|' | '`' => self.narrow,
+            'm' | 'w' | 'M' | 'W' | '@' | '%' => self.wide,
+            '0'..='9' => self.digit,
+            ch if ch.is_uppercase() => self.uppercase,
+            _ => self.lowercase,
+        }
+    }
+
+    pub fn text_width(&self, str: &str, font_size: f32) -> f32 {
+        str.chars().map(|ch| self.advance(ch)).sum::<f32>() * font_size
+    }
+
+    /// Measures the text laid out in lines broken on whitespace to fit in `max_width`,
+    /// words that don't fit on their own overflow the line.
+    pub fn measure(&self, str: &str, font_size: f32, max_width: Option<f32>) -> (f32, f32) {
+        let lines = self.wrap(str, font_size, max_width);
+        let width = lines
+            .iter()
+            .map(|line| self.text_width(line, font_size))
+            .fold(0f32, f32::max);
+        let height = lines.len() as f32 * self.line_height * font_size;
+        (width, height)
+    }
+
+    pub fn wrap(&self, str: &str, font_size: f32, max_width: Option<f32>) -> Vec<String> {
+        let mut lines = vec![];
+        for paragraph in str.trim().lines() {
+            let max_width = match max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph.trim().to_owned());
+                    continue;
+                }
+            };
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                if !line.is_empty() {
+                    let candidate = format!("{} {}", line, word);
+                    // a small tolerance keeps text measured earlier from wrapping at its own width
+                    if self.text_width(&candidate, font_size) > max_width + 0.01 {
+                        lines.push(std::mem::replace(&mut line, word.to_owned()));
+                    } else {
+                        line = candidate;
+                    }
+                } else {
+                    line.push_str(word);
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FontMetrics, DEFAULT_FONT_FAMILY};
+
+    #[test]
+    fn it_measures_single_line() {
+        let font = FontMetrics::for_family(DEFAULT_FONT_FAMILY);
+        let (width, height) = font.measure("Hello", 10.0, None);
+        assert!((width - font.text_width("Hello", 10.0)).abs() < f32::EPSILON);
+        assert!((height - 12.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn it_wraps_to_max_width() {
+        let font = FontMetrics::for_family(DEFAULT_FONT_FAMILY);
+        let word = font.text_width("word", 10.0);
+        let lines = font.wrap("word word word", 10.0, Some(font.text_width("word word", 10.0)));
+        assert_eq!(lines, vec!["word word", "word"]);
+
+        let (width, height) = font.measure("word word word", 10.0, Some(word));
+        assert_eq!(width, word);
+        assert!((height - 36.0).abs() < 1e-4);
+    }
+}
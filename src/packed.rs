@@ -0,0 +1,411 @@
+//! The layout record exchanged with Redscript as a single array of floats,
+//! the order of the fields has to match `Layout.GetData` and `Layout.SetData` in `Layout.reds`.
+use anyhow::{anyhow, Result};
+use flexlayout_rs::{Dimension, FlexAlign, FlexDirection, FlexWrap};
+
+use crate::tree::{Display, Edges, Layout, Overflow, PositionType};
+
+pub const LEN: usize = 47;
+
+pub fn pack(layout: &Layout) -> Vec<f32> {
+    let mut data = Vec::with_capacity(LEN);
+    data.push(position_type_value(layout.position_type) as f32);
+    data.push(flex_direction_value(layout.flex_direction) as f32);
+    data.push(flex_wrap_value(layout.flex_wrap) as f32);
+    data.push(flex_align_value(layout.align_items) as f32);
+    data.push(flex_align_value(layout.align_content) as f32);
+    data.push(flex_align_value(layout.justify_content) as f32);
+    data.push(flex_align_value(layout.align_self) as f32);
+    for dim in [
+        layout.width,
+        layout.height,
+        layout.min_width,
+        layout.max_width,
+        layout.min_height,
+        layout.max_height,
+        layout.left,
+        layout.right,
+        layout.top,
+        layout.bottom,
+        layout.flex_basis,
+    ] {
+        let (unit, val) = match dim {
+            Dimension::Auto => (0f32, 0f32),
+            Dimension::Point(val) => (1f32, val),
+            Dimension::Percent(val) => (2f32, val),
+        };
+        data.push(unit);
+        data.push(val);
+    }
+    for edges in [layout.margin, layout.padding] {
+        data.extend_from_slice(&[edges.left, edges.right, edges.top, edges.bottom]);
+    }
+    data.push(layout.flex_grow);
+    data.push(layout.flex_shrink);
+    data.push(display_value(layout.display) as f32);
+    let border = layout.border;
+    data.extend_from_slice(&[border.left, border.right, border.top, border.bottom]);
+    data.push(overflow_value(layout.overflow) as f32);
+    data.push(layout.row_gap);
+    data.push(layout.column_gap);
+    data
+}
+
+pub fn unpack(data: &[f32]) -> Result<Layout> {
+    if data.len() != LEN {
+        return Err(anyhow!(
+            "Layout record has {} fields, expected {}",
+            data.len(),
+            LEN
+        ));
+    }
+    let mut reader = Reader { data, pos: 0 };
+    Ok(Layout {
+        position_type: reader.enumeration("PositionType", position_type)?,
+        flex_direction: reader.enumeration("FlexDirection", flex_direction)?,
+        flex_wrap: reader.enumeration("FlexWrap", flex_wrap)?,
+        align_items: reader.enumeration("FlexAlign", flex_align)?,
+        align_content: reader.enumeration("FlexAlign", flex_align)?,
+        justify_content: reader.enumeration("FlexAlign", flex_align)?,
+        align_self: reader.enumeration("FlexAlign", flex_align)?,
+        width: reader.dimension()?,
+        height: reader.dimension()?,
+        min_width: reader.dimension()?,
+        max_width: reader.dimension()?,
+        min_height: reader.dimension()?,
+        max_height: reader.dimension()?,
+        left: reader.dimension()?,
+        right: reader.dimension()?,
+        top: reader.dimension()?,
+        bottom: reader.dimension()?,
+        flex_basis: reader.dimension()?,
+        margin: reader.edges(),
+        padding: reader.edges(),
+        flex_grow: reader.float(),
+        flex_shrink: reader.float(),
+        display: reader.enumeration("Display", display)?,
+        border: reader.edges(),
+        overflow: reader.enumeration("Overflow", overflow)?,
+        row_gap: reader.float(),
+        column_gap: reader.float(),
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [f32],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn float(&mut self) -> f32 {
+        let val = self.data[self.pos];
+        self.pos += 1;
+        val
+    }
+
+    fn enumeration<A>(&mut self, name: &str, decode: fn(u64) -> Option<A>) -> Result<A> {
+        let pos = self.pos;
+        let val = self.float();
+        decode(val as u64).ok_or_else(|| anyhow!("Invalid {} value {} at field {}", name, val, pos))
+    }
+
+    fn dimension(&mut self) -> Result<Dimension> {
+        let pos = self.pos;
+        let unit = self.float();
+        let val = self.float();
+        dimension(unit as u64, val).ok_or_else(|| anyhow!("Invalid Unit value {} at field {}", unit, pos))
+    }
+
+    fn edges(&mut self) -> Edges {
+        Edges {
+            left: self.float(),
+            right: self.float(),
+            top: self.float(),
+            bottom: self.float(),
+        }
+    }
+}
+
+/// The values of the enums shared with Redscript as expected by the decoders below
+/// and produced by their inverses.
+pub const ENUMS: &[(&str, &[(&str, u64)])] = &[
+    ("Unit", &[("Auto", 0), ("Point", 1), ("Percent", 2)]),
+    ("FlexDirection", &[
+        ("Row", 0),
+        ("Column", 1),
+        ("RowReverse", 2),
+        ("ColumnReverse", 3),
+    ]),
+    ("FlexWrap", &[("NoWrap", 0), ("Wrap", 1), ("WrapReverse", 2)]),
+    ("FlexAlign", &[
+        ("Inherit", 0),
+        ("Stretch", 1),
+        ("Start", 2),
+        ("Center", 3),
+        ("End", 4),
+        ("SpaceBetween", 5),
+        ("SpaceAround", 6),
+        ("Baseline", 7),
+    ]),
+    ("PositionType", &[("Relative", 0), ("Absolute", 1)]),
+    ("Display", &[("Flex", 0), ("None", 1)]),
+    ("Overflow", &[("Visible", 0), ("Hidden", 1), ("Scroll", 2)]),
+];
+
+/// Compares enum values reported by Redscript as `Enum.Variant=value` lines
+/// against [ENUMS] and describes every mismatch found.
+pub fn check_enums(reported: &[String]) -> Vec<String> {
+    let mut mismatches = vec![];
+    for (name, variants) in ENUMS {
+        for (variant, expected) in *variants {
+            let key = format!("{}.{}=", name, variant);
+            let actual = reported
+                .iter()
+                .find_map(|line| line.strip_prefix(&key))
+                .map(|val| val.trim().parse::<u64>());
+            match actual {
+                Some(Ok(val)) if val == *expected => {}
+                Some(Ok(val)) => mismatches.push(format!(
+                    "{}.{} is {} in Redscript, expected {}",
+                    name, variant, val, expected
+                )),
+                Some(Err(_)) => mismatches.push(format!("{}.{} has an invalid value", name, variant)),
+                None => mismatches.push(format!("{}.{} is missing in Redscript", name, variant)),
+            }
+        }
+    }
+    mismatches
+}
+
+pub fn dimension(unit: u64, val: f32) -> Option<Dimension> {
+    match unit {
+        0 => Some(Dimension::Auto),
+        1 => Some(Dimension::Point(val)),
+        2 => Some(Dimension::Percent(val)),
+        _ => None,
+    }
+}
+
+pub fn position_type(val: u64) -> Option<PositionType> {
+    match val {
+        0 => Some(PositionType::Relative),
+        1 => Some(PositionType::Absolute),
+        _ => None,
+    }
+}
+
+pub fn position_type_value(val: PositionType) -> u64 {
+    match val {
+        PositionType::Relative => 0,
+        PositionType::Absolute => 1,
+    }
+}
+
+pub fn display(val: u64) -> Option<Display> {
+    match val {
+        0 => Some(Display::Flex),
+        1 => Some(Display::None),
+        _ => None,
+    }
+}
+
+pub fn display_value(val: Display) -> u64 {
+    match val {
+        Display::Flex => 0,
+        Display::None => 1,
+    }
+}
+
+pub fn overflow(val: u64) -> Option<Overflow> {
+    match val {
+        0 => Some(Overflow::Visible),
+        1 => Some(Overflow::Hidden),
+        2 => Some(Overflow::Scroll),
+        _ => None,
+    }
+}
+
+pub fn overflow_value(val: Overflow) -> u64 {
+    match val {
+        Overflow::Visible => 0,
+        Overflow::Hidden => 1,
+        Overflow::Scroll => 2,
+    }
+}
+
+pub fn flex_direction(val: u64) -> Option<FlexDirection> {
+    match val {
+        0 => Some(FlexDirection::Row),
+        1 => Some(FlexDirection::Column),
+        2 => Some(FlexDirection::RowReverse),
+        3 => Some(FlexDirection::ColumnReverse),
+        _ => None,
+    }
+}
+
+pub fn flex_direction_value(val: FlexDirection) -> u64 {
+    match val {
+        FlexDirection::Row => 0,
+        FlexDirection::Column => 1,
+        FlexDirection::RowReverse => 2,
+        FlexDirection::ColumnReverse => 3,
+    }
+}
+
+pub fn flex_wrap(val: u64) -> Option<FlexWrap> {
+    match val {
+        0 => Some(FlexWrap::NoWrap),
+        1 => Some(FlexWrap::Wrap),
+        2 => Some(FlexWrap::WrapReverse),
+        _ => None,
+    }
+}
+
+pub fn flex_wrap_value(val: FlexWrap) -> u64 {
+    match val {
+        FlexWrap::NoWrap => 0,
+        FlexWrap::Wrap => 1,
+        FlexWrap::WrapReverse => 2,
+    }
+}
+
+pub fn flex_align(val: u64) -> Option<FlexAlign> {
+    match val {
+        0 => Some(FlexAlign::Inherit),
+        1 => Some(FlexAlign::Stretch),
+        2 => Some(FlexAlign::Start),
+        3 => Some(FlexAlign::Center),
+        4 => Some(FlexAlign::End),
+        5 => Some(FlexAlign::SpaceBetween),
+        6 => Some(FlexAlign::SpaceAround),
+        7 => Some(FlexAlign::Baseline),
+        _ => None,
+    }
+}
+
+pub fn flex_align_value(val: FlexAlign) -> u64 {
+    match val {
+        FlexAlign::Inherit => 0,
+        FlexAlign::Stretch => 1,
+        FlexAlign::Start => 2,
+        FlexAlign::Center => 3,
+        FlexAlign::End => 4,
+        FlexAlign::SpaceBetween => 5,
+        FlexAlign::SpaceAround => 6,
+        FlexAlign::Baseline => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flexlayout_rs::{Dimension, FlexAlign, FlexDirection};
+
+    use super::{check_enums, pack, unpack, ENUMS, LEN};
+    use crate::packed;
+    use crate::tree::{Display, Edges, Layout, Overflow, PositionType};
+
+    #[test]
+    fn it_round_trips_layouts() -> anyhow::Result<()> {
+        let layout = Layout {
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            align_self: FlexAlign::End,
+            width: Dimension::Percent(50.0),
+            max_height: Dimension::Point(120.0),
+            left: Dimension::Point(4.0),
+            margin: Edges::new(1.0, 2.0, 3.0, 4.0),
+            border: Edges::new(2.0, 0.0, 2.0, 0.0),
+            flex_grow: 2.0,
+            display: Display::None,
+            overflow: Overflow::Scroll,
+            column_gap: 6.0,
+            ..Layout::default()
+        };
+        let data = pack(&layout);
+        assert_eq!(data.len(), LEN);
+
+        let res = unpack(&data)?;
+        assert_eq!(res.position_type, PositionType::Absolute);
+        assert!(matches!(res.flex_direction, FlexDirection::ColumnReverse));
+        assert!(matches!(res.align_self, FlexAlign::End));
+        assert!(matches!(res.width, Dimension::Percent(v) if v == 50.0));
+        assert!(matches!(res.max_height, Dimension::Point(v) if v == 120.0));
+        assert!(matches!(res.left, Dimension::Point(v) if v == 4.0));
+        assert!(matches!(res.height, Dimension::Auto));
+        assert_eq!(res.margin, layout.margin);
+        assert_eq!(res.border, layout.border);
+        assert_eq!(res.flex_grow, 2.0);
+        assert_eq!(res.flex_shrink, 1.0);
+        assert_eq!(res.display, Display::None);
+        assert_eq!(res.overflow, Overflow::Scroll);
+        assert_eq!(res.row_gap, 0.0);
+        assert_eq!(res.column_gap, 6.0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_encodes_enums_as_listed() {
+        fn values(name: &str) -> Vec<u64> {
+            let (_, variants) = ENUMS.iter().find(|(enum_name, _)| *enum_name == name).unwrap();
+            variants.iter().map(|(_, val)| *val).collect()
+        }
+        for val in values("PositionType") {
+            assert_eq!(
+                packed::position_type(val).map(packed::position_type_value),
+                Some(val)
+            );
+        }
+        for val in values("Display") {
+            assert_eq!(packed::display(val).map(packed::display_value), Some(val));
+        }
+        for val in values("Overflow") {
+            assert_eq!(packed::overflow(val).map(packed::overflow_value), Some(val));
+        }
+        for val in values("FlexDirection") {
+            assert_eq!(
+                packed::flex_direction(val).map(packed::flex_direction_value),
+                Some(val)
+            );
+        }
+        for val in values("FlexWrap") {
+            assert_eq!(packed::flex_wrap(val).map(packed::flex_wrap_value), Some(val));
+        }
+        for val in values("FlexAlign") {
+            assert_eq!(packed::flex_align(val).map(packed::flex_align_value), Some(val));
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_records() {
+        assert!(unpack(&[0.0; 3]).is_err());
+
+        let mut data = pack(&Layout::default());
+        data[3] = 12.0;
+        assert!(unpack(&data).is_err());
+    }
+
+    #[test]
+    fn it_reports_enum_mismatches() {
+        let mut reported: Vec<String> = ENUMS
+            .iter()
+            .flat_map(|(name, variants)| {
+                variants
+                    .iter()
+                    .map(move |(variant, val)| format!("{}.{}={}", name, variant, val))
+            })
+            .collect();
+        assert!(check_enums(&reported).is_empty());
+
+        reported.retain(|line| !line.starts_with("Unit.Percent"));
+        for line in reported
+            .iter_mut()
+            .filter(|line| line.starts_with("FlexAlign.End"))
+        {
+            *line = "FlexAlign.End=3".to_owned();
+        }
+        assert_eq!(check_enums(&reported), vec![
+            "Unit.Percent is missing in Redscript",
+            "FlexAlign.End is 3 in Redscript, expected 4"
+        ]);
+    }
+}
@@ -1,6 +1,10 @@
-use flexlayout_rs::{Dimension, FlexAlign, FlexDirection, FlexProperty, FlexWrap};
-use red4ext_rs::interop::{Color, IsoRED};
+use flexlayout_rs::{Dimension, FlexAlign, FlexDirection, FlexWrap};
+use red4ext_rs::interop::{CName, Color, IsoRED, Vector2};
 use red4ext_rs::prelude::*;
+use red4ext_rs::rtti;
+
+use crate::tree::{self, Display, ElemKind, Overflow, PositionType};
+use crate::{binding, layout, packed};
 
 #[derive(Clone, Default)]
 #[repr(C)]
@@ -16,6 +20,58 @@ impl IsoRED for Elem {
 }
 
 impl Elem {
+    /// Creates the script elements of the tree, the event handlers of the elements call methods
+    /// of the target object.
+    pub fn from_tree(elem: &tree::Elem, target: &Ref<RED4ext::IScriptable>) -> Self {
+        let children = elem.children.iter().map(|child| Elem::from_tree(child, target));
+        let mut res = match &elem.kind {
+            ElemKind::Box {
+                background_color,
+                border_color,
+                border_radius,
+            } => {
+                let elem = Elem::new_box(children, background_color.map(Into::into));
+                if let Some(color) = border_color {
+                    call!(elem.repr.clone(), "BorderColor" (Color::from(*color)) -> Elem);
+                }
+                if let Some(radius) = border_radius {
+                    call!(elem.repr.clone(), "BorderRadius" (*radius) -> Elem);
+                }
+                elem
+            }
+            ElemKind::Text {
+                text,
+                font_size,
+                color,
+            } => Elem::new_text(text, *font_size, color.map(Into::into)),
+            ElemKind::Image {
+                atlas,
+                part,
+                tint,
+                nine_slice,
+            } => Elem::new_image(atlas, part.as_deref(), tint.map(Into::into), *nine_slice),
+            ElemKind::Custom { class, attributes } => Elem::new_custom(class, attributes),
+        };
+        let res = res.with_layout(Layout::from_tree(&elem.layout));
+        if let Some(id) = &elem.id {
+            call!(res.repr.clone(), "Id" (id.as_str()) -> Elem);
+        }
+        if elem.appearance != tree::Appearance::default() {
+            call!(res.repr.clone(), "Opacity" (elem.appearance.opacity) -> Elem);
+            call!(res.repr.clone(), "Visible" (elem.appearance.visible) -> Elem);
+        }
+        if !elem.handlers.is_empty() && target.instance.is_null() {
+            // without a target the handlers are left out and the widget stays non-interactive
+            log("[Flexy] Event handlers require a target, it can be set with Bindings.Target");
+        } else {
+            for handler in &elem.handlers {
+                let event = handler.event.callback();
+                call!(res.repr.clone(), "AddHandler" (event, target.clone(), handler.method.as_str()) -> Elem);
+            }
+        }
+        res
+    }
+
     pub fn new_box(children: impl IntoIterator<Item = Elem>, color: Option<Color>) -> Self {
         let elem = call!("Flexy.UI.Box::New;" () -> Elem);
         for child in children {
@@ -50,17 +106,89 @@ impl Elem {
         elem
     }
 
+    /// Instantiates the script class registered for a custom tag and passes it the attributes
+    /// through `SetAttribute`, an empty box is created instead if the class doesn't exist.
+    pub fn new_custom(class: &str, attributes: &[(String, String)]) -> Self {
+        let repr = new_object(class);
+        if repr.instance.is_null() {
+            log(&format!("[Flexy] Unknown element class {}", class));
+            return Elem::new_box([], None);
+        }
+        let elem = Elem { repr };
+        for (name, val) in attributes {
+            call!(elem.repr.clone(), "SetAttribute" (name.as_str(), val.as_str()) -> ());
+        }
+        elem
+    }
+
+    /// Returns the address of the underlying script object, it identifies the element.
+    #[inline]
+    pub fn addr(&self) -> usize {
+        self.repr.instance as usize
+    }
+
+    pub fn id(&self) -> Option<String> {
+        let id = call!(self.repr.clone(), "GetId" () -> String);
+        (!id.is_empty()).then_some(id)
+    }
+
     pub fn layout(&self) -> Layout {
         call!(self.repr.clone(), "GetLayout" () -> Layout)
     }
 
+    /// Reads the whole layout of the element in a single call.
+    pub fn layout_data(&self) -> tree::Layout {
+        let data = call!(self.repr.clone(), "GetLayoutData" () -> Vec<f32>);
+        packed::unpack(&data).unwrap_or_else(|err| {
+            log(&format!("[Flexy] Invalid layout data: {}", err));
+            tree::Layout::default()
+        })
+    }
+
     pub fn children(&self) -> Vec<Elem> {
         call!(self.repr.clone(), "GetChildren" () -> Vec<Elem>)
     }
 
+    pub fn measure(&self, max_width: Option<f32>) -> Vector2 {
+        call!(self.repr.clone(), "Measure" (max_width.unwrap_or(-1f32)) -> Vector2)
+    }
+
     pub fn with_layout(&mut self, layout: Layout) -> Self {
         call!(self.repr.clone(), "Layout" (layout) -> Elem)
     }
+
+    /// Applies the opacity and the visibility of the element to its widget.
+    pub fn apply_appearance(&self, widget: &Widget) {
+        call!(self.repr.clone(), "ApplyAppearance" (widget.clone()) -> ())
+    }
+
+    pub fn attach_child(&self, widget: &Widget, child: &Widget) {
+        call!(self.repr.clone(), "AttachChild" (widget.clone(), child.clone()) -> ())
+    }
+
+    pub fn detach_child(&self, widget: &Widget, child: &Widget) {
+        call!(self.repr.clone(), "DetachChild" (widget.clone(), child.clone()) -> ())
+    }
+
+    pub fn set_mounted(&self, mounted: bool) {
+        call!(self.repr.clone(), "SetMounted" (mounted) -> ())
+    }
+
+    /// Inserts the widget of a child before the widgets of the children that come after it.
+    pub fn insert_child(&self, widget: &Widget, child: &Widget, index: usize) {
+        call!(self.repr.clone(), "InsertChild" (widget.clone(), child.clone(), index as i32) -> ())
+    }
+
+    /// Reports the size of the content of an element that clips its children, see [layout::content_size].
+    pub fn set_content_size(&self, size: Vector2) {
+        call!(self.repr.clone(), "SetContentSize" (size) -> ())
+    }
+
+    /// Registers the ink callbacks of the event handlers that haven't been bound to the widget yet,
+    /// a new widget gets all of them.
+    pub fn bind_handlers(&self, widget: &Widget, new_widget: bool) {
+        call!(self.repr.clone(), "BindHandlers" (widget.clone(), new_widget) -> ())
+    }
 }
 
 #[derive(Clone, Default)]
@@ -81,113 +209,147 @@ impl Layout {
         call!("Flexy.Layout.Layout::New;" () -> Layout)
     }
 
-    pub fn position_type(&self) -> PositionType {
-        match call!(self.repr.clone(), "GetPositionType" () -> u64) {
-            0 => PositionType::Relative,
-            1 => PositionType::Absolute,
-            _ => panic!(),
+    pub fn from_tree(layout: &tree::Layout) -> Self {
+        let mut res = Self::new();
+        res.with_data(packed::pack(layout))
+    }
+
+    pub fn with_data(&mut self, data: Vec<f32>) -> Self {
+        call!(self.repr.clone(), "SetData" (data) -> Self)
+    }
+
+    /// Reads the layout with a separate call for every property,
+    /// it's only kept as a baseline for benchmarking the packed transfer.
+    pub fn read_fields(&self) -> tree::Layout {
+        tree::Layout {
+            position_type: self.position_type(),
+            flex_direction: self.flex_direction(),
+            flex_wrap: self.flex_wrap(),
+            align_items: self.align_items(),
+            align_content: self.align_content(),
+            justify_content: self.justify_content(),
+            align_self: self.align_self(),
+            width: self.width(),
+            height: self.height(),
+            min_width: self.min_width(),
+            max_width: self.max_width(),
+            min_height: self.min_height(),
+            max_height: self.max_height(),
+            left: self.left(),
+            right: self.right(),
+            top: self.top(),
+            bottom: self.bottom(),
+            margin: tree::Edges {
+                left: self.margin_left(),
+                right: self.margin_right(),
+                top: self.margin_top(),
+                bottom: self.margin_bottom(),
+            },
+            padding: tree::Edges {
+                left: self.padding_left(),
+                right: self.padding_right(),
+                top: self.padding_top(),
+                bottom: self.padding_bottom(),
+            },
+            border: tree::Edges {
+                left: self.border_left(),
+                right: self.border_right(),
+                top: self.border_top(),
+                bottom: self.border_bottom(),
+            },
+            flex_grow: self.flex_grow(),
+            flex_shrink: self.flex_shrink(),
+            flex_basis: self.flex_basis(),
+            display: self.display(),
+            overflow: self.overflow(),
+            row_gap: self.row_gap(),
+            column_gap: self.column_gap(),
         }
     }
 
-    pub fn with_position_type(&mut self, typ: PositionType) -> Self {
-        call!(self.repr.clone(), "PositionType" (typ as u64) -> Self)
+    pub fn position_type(&self) -> PositionType {
+        let val = call!(self.repr.clone(), "GetPositionType" () -> u64);
+        decode_enum("PositionType", val, packed::position_type, PositionType::Relative)
     }
 
     pub fn flex_direction(&self) -> FlexDirection {
-        match call!(self.repr.clone(), "GetFlexDirection" () -> u64) {
-            0 => FlexDirection::Row,
-            1 => FlexDirection::Column,
-            2 => FlexDirection::RowReverse,
-            3 => FlexDirection::ColumnReverse,
-            _ => panic!(),
-        }
-    }
-
-    pub fn with_flex_direction(&mut self, dir: FlexDirection) -> Self {
-        call!(self.repr.clone(), "FlexDirection" (dir as u64) -> Self)
+        let val = call!(self.repr.clone(), "GetFlexDirection" () -> u64);
+        decode_enum("FlexDirection", val, packed::flex_direction, FlexDirection::Row)
     }
 
     pub fn flex_wrap(&self) -> FlexWrap {
-        match call!(self.repr.clone(), "GetFlexWrap" () -> u64) {
-            0 => FlexWrap::NoWrap,
-            1 => FlexWrap::Wrap,
-            2 => FlexWrap::WrapReverse,
-            _ => panic!(),
-        }
-    }
-
-    pub fn with_flex_wrap(&mut self, wrap: FlexWrap) -> Self {
-        call!(self.repr.clone(), "FlexWrap" (wrap as u64) -> Self)
+        let val = call!(self.repr.clone(), "GetFlexWrap" () -> u64);
+        decode_enum("FlexWrap", val, packed::flex_wrap, FlexWrap::NoWrap)
     }
 
     pub fn align_content(&self) -> FlexAlign {
-        match call!(self.repr.clone(), "GetAlignContent" () -> u64) {
-            0 => FlexAlign::Inherit,
-            1 => FlexAlign::Stretch,
-            2 => FlexAlign::Start,
-            3 => FlexAlign::Center,
-            4 => FlexAlign::End,
-            5 => FlexAlign::SpaceBetween,
-            6 => FlexAlign::SpaceAround,
-            7 => FlexAlign::Baseline,
-            _ => panic!(),
-        }
+        let val = call!(self.repr.clone(), "GetAlignContent" () -> u64);
+        decode_enum("FlexAlign", val, packed::flex_align, FlexAlign::Inherit)
     }
 
-    pub fn with_align_content(&mut self, align: FlexAlign) -> Self {
-        call!(self.repr.clone(), "AlignContent" (align as u64) -> Self)
+    pub fn align_items(&self) -> FlexAlign {
+        let val = call!(self.repr.clone(), "GetAlignItems" () -> u64);
+        decode_enum("FlexAlign", val, packed::flex_align, FlexAlign::Inherit)
     }
 
-    pub fn align_items(&self) -> FlexAlign {
-        match call!(self.repr.clone(), "GetAlignItems" () -> u64) {
-            0 => FlexAlign::Inherit,
-            1 => FlexAlign::Stretch,
-            2 => FlexAlign::Start,
-            3 => FlexAlign::Center,
-            4 => FlexAlign::End,
-            5 => FlexAlign::SpaceBetween,
-            6 => FlexAlign::SpaceAround,
-            7 => FlexAlign::Baseline,
-            _ => panic!(),
-        }
+    pub fn justify_content(&self) -> FlexAlign {
+        let val = call!(self.repr.clone(), "GetJustifyContent" () -> u64);
+        decode_enum("FlexAlign", val, packed::flex_align, FlexAlign::Inherit)
     }
 
-    pub fn with_align_items(&mut self, align: FlexAlign) -> Self {
-        call!(self.repr.clone(), "AlignItems" (align as u64) -> Self)
+    pub fn align_self(&self) -> FlexAlign {
+        let val = call!(self.repr.clone(), "GetAlignSelf" () -> u64);
+        decode_enum("FlexAlign", val, packed::flex_align, FlexAlign::Inherit)
     }
 
-    pub fn justify_content(&self) -> FlexAlign {
-        match call!(self.repr.clone(), "GetJustifyContent" () -> u64) {
-            0 => FlexAlign::Inherit,
-            1 => FlexAlign::Stretch,
-            2 => FlexAlign::Start,
-            3 => FlexAlign::Center,
-            4 => FlexAlign::End,
-            5 => FlexAlign::SpaceBetween,
-            6 => FlexAlign::SpaceAround,
-            7 => FlexAlign::Baseline,
-            _ => panic!(),
-        }
+    pub fn display(&self) -> Display {
+        let val = call!(self.repr.clone(), "GetDisplay" () -> u64);
+        decode_enum("Display", val, packed::display, Display::Flex)
     }
 
-    pub fn with_justify_content(&mut self, align: FlexAlign) -> Self {
-        call!(self.repr.clone(), "JustifyContent" (align as u64) -> Self)
+    pub fn overflow(&self) -> Overflow {
+        let val = call!(self.repr.clone(), "GetOverflow" () -> u64);
+        decode_enum("Overflow", val, packed::overflow, Overflow::Visible)
     }
 
     pub fn height(&self) -> Dimension {
         Self::create_dim(call!(self.repr.clone(), "GetHeight" () -> Ref<RED4ext::IScriptable>))
     }
 
-    pub fn with_height(&mut self, str: &str) -> Self {
-        call!(self.repr.clone(), "Height" (str) -> Self)
-    }
-
     pub fn width(&self) -> Dimension {
         Self::create_dim(call!(self.repr.clone(), "GetWidth" () -> Ref<RED4ext::IScriptable>))
     }
 
-    pub fn with_width(&mut self, str: &str) -> Self {
-        call!(self.repr.clone(), "Width" (str) -> Self)
+    pub fn min_width(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetMinWidth" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn max_width(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetMaxWidth" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn min_height(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetMinHeight" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn max_height(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetMaxHeight" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn left(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetLeft" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn right(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetRight" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn top(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetTop" () -> Ref<RED4ext::IScriptable>))
+    }
+
+    pub fn bottom(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetBottom" () -> Ref<RED4ext::IScriptable>))
     }
 
     pub fn margin_left(&self) -> f32 {
@@ -206,10 +368,6 @@ impl Layout {
         call!(self.repr.clone(), "GetMarginBottom" () -> f32)
     }
 
-    pub fn with_margin(&mut self, val: f32) -> Self {
-        call!(self.repr.clone(), "Margin" (val) -> Self)
-    }
-
     pub fn padding_left(&self) -> f32 {
         call!(self.repr.clone(), "GetPaddingLeft" () -> f32)
     }
@@ -226,38 +384,40 @@ impl Layout {
         call!(self.repr.clone(), "GetPaddingBottom" () -> f32)
     }
 
-    pub fn with_padding(&mut self, val: f32) -> Self {
-        call!(self.repr.clone(), "Padding" (val) -> Self)
+    pub fn border_left(&self) -> f32 {
+        call!(self.repr.clone(), "GetBorderLeft" () -> f32)
+    }
+
+    pub fn border_right(&self) -> f32 {
+        call!(self.repr.clone(), "GetBorderRight" () -> f32)
+    }
+
+    pub fn border_top(&self) -> f32 {
+        call!(self.repr.clone(), "GetBorderTop" () -> f32)
+    }
+
+    pub fn border_bottom(&self) -> f32 {
+        call!(self.repr.clone(), "GetBorderBottom" () -> f32)
+    }
+
+    pub fn row_gap(&self) -> f32 {
+        call!(self.repr.clone(), "GetRowGap" () -> f32)
+    }
+
+    pub fn column_gap(&self) -> f32 {
+        call!(self.repr.clone(), "GetColumnGap" () -> f32)
     }
 
     pub fn flex_grow(&self) -> f32 {
         call!(self.repr.clone(), "GetFlexGrow" () -> f32)
     }
 
-    pub fn with_flex_grow(&mut self, val: f32) -> Self {
-        call!(self.repr.clone(), "FlexGrow" (val) -> Self)
-    }
-
-    pub fn properties(&self) -> Vec<FlexProperty> {
-        vec![
-            FlexProperty::Direction(self.flex_direction()),
-            FlexProperty::Wrap(self.flex_wrap()),
-            FlexProperty::AlignItems(self.align_items()),
-            FlexProperty::AlignContent(self.align_content()),
-            FlexProperty::JustifyContent(self.justify_content()),
-            FlexProperty::Height(self.height()),
-            FlexProperty::Width(self.width()),
-            FlexProperty::MarginLeft(self.margin_left()),
-            FlexProperty::MarginRight(self.margin_right()),
-            FlexProperty::MarginTop(self.margin_top()),
-            FlexProperty::MarginBottom(self.margin_bottom()),
-            FlexProperty::PaddingLeft(self.padding_left()),
-            FlexProperty::PaddingRight(self.padding_right()),
-            FlexProperty::PaddingTop(self.padding_top()),
-            FlexProperty::PaddingBottom(self.padding_bottom()),
-            FlexProperty::Grow(self.flex_grow()),
-            FlexProperty::Fixed(self.position_type() == PositionType::Absolute),
-        ]
+    pub fn flex_shrink(&self) -> f32 {
+        call!(self.repr.clone(), "GetFlexShrink" () -> f32)
+    }
+
+    pub fn flex_basis(&self) -> Dimension {
+        Self::create_dim(call!(self.repr.clone(), "GetFlexBasis" () -> Ref<RED4ext::IScriptable>))
     }
 
     fn create_dim(dim: Ref<RED4ext::IScriptable>) -> Dimension {
@@ -265,38 +425,198 @@ impl Layout {
             Dimension::Auto
         } else {
             let val = call!(dim.clone(), "GetValue" () -> f32);
-            match call!(dim, "GetUnit" () -> DimensionUnit) {
-                DimensionUnit::Auto => Dimension::Auto,
-                DimensionUnit::Point => Dimension::Point(val),
-                DimensionUnit::Percent => Dimension::Percent(val),
-            }
+            let unit = call!(dim, "GetUnit" () -> u64);
+            decode_enum("Unit", unit, |unit| packed::dimension(unit, val), Dimension::Auto)
         }
     }
 }
 
+pub fn new_dim(dim: Dimension) -> Ref<RED4ext::IScriptable> {
+    let (val, unit) = match dim {
+        Dimension::Auto => (0f32, DimensionUnit::Auto),
+        Dimension::Point(val) => (val, DimensionUnit::Point),
+        Dimension::Percent(val) => (val, DimensionUnit::Percent),
+    };
+    call!("Flexy.Layout.Dim::New;FloatUnit" (val, unit) -> Ref<RED4ext::IScriptable>)
+}
+
+/// Decodes an enum value read from script, values unknown to the plugin are reported
+/// and replaced with the fallback.
+fn decode_enum<A>(name: &str, val: u64, decode: impl FnOnce(u64) -> Option<A>, fallback: A) -> A {
+    decode(val).unwrap_or_else(|| {
+        log(&format!("[Flexy] Invalid {} value {}", name, val));
+        fallback
+    })
+}
+
+/// Creates an instance of a script class by name, the reference is null if there's no such class.
+fn new_object(class: &str) -> Ref<RED4ext::IScriptable> {
+    let cls = rtti::get_class(CName::new(class));
+    if cls.is_null() {
+        Ref::default()
+    } else {
+        rtti::new_object(cls)
+    }
+}
+
+/// Returns the values of the shared enums as declared in script, see `packed::check_enums`.
+pub fn describe_enums() -> Vec<String> {
+    call!("Flexy.Layout.DescribeEnums;" () -> Vec<String>)
+}
+
+pub fn log(msg: &str) {
+    call!("LogError;String" (msg) -> ())
+}
+
+impl From<tree::Color> for Color {
+    #[inline]
+    fn from(color: tree::Color) -> Self {
+        Color::new(color.red, color.green, color.blue, color.alpha)
+    }
+}
+
 #[derive(Clone, Default)]
 #[repr(C)]
-pub struct Widget {
+pub struct Bindings {
     pub(crate) repr: Ref<RED4ext::IScriptable>,
 }
 
-impl IsoRED for Widget {
+impl IsoRED for Bindings {
     #[inline]
     fn type_name() -> &'static str {
-        "ref<inkWidget>"
+        "ref<Bindings>"
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u64)]
-pub enum PositionType {
-    Relative = 0,
-    Absolute = 1,
+impl Bindings {
+    /// Reads all of the variables, a null reference has no variables.
+    pub fn read(&self) -> binding::Bindings {
+        let mut res = binding::Bindings::default();
+        if self.repr.instance.is_null() {
+            return res;
+        }
+        let entries = call!(self.repr.clone(), "GetEntries" () -> Vec<String>);
+        for entry in entries.chunks_exact(2) {
+            res.set(&entry[0], &entry[1]);
+        }
+
+        let lists = call!(self.repr.clone(), "GetListNames" () -> Vec<String>);
+        for (idx, name) in lists.iter().enumerate() {
+            let idx = idx as i32;
+            let values = call!(self.repr.clone(), "GetListValues" (idx) -> Vec<String>);
+            let items = if values.is_empty() {
+                call!(self.repr.clone(), "GetListItems" (idx) -> Vec<Bindings>)
+                    .iter()
+                    .map(|item| binding::Value::Map(item.read()))
+                    .collect()
+            } else {
+                values.into_iter().map(binding::Value::Str).collect()
+            };
+            res.set_value(name, binding::Value::List(items));
+        }
+        res
+    }
+
+    /// Returns the object the event handlers of the rendered elements are called on.
+    pub fn target(&self) -> Ref<RED4ext::IScriptable> {
+        if self.repr.instance.is_null() {
+            return Ref::default();
+        }
+        call!(self.repr.clone(), "GetTarget" () -> Ref<RED4ext::IScriptable>)
+    }
 }
 
-impl Default for PositionType {
-    fn default() -> Self {
-        PositionType::Relative
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct TemplateListener {
+    pub(crate) repr: Ref<RED4ext::IScriptable>,
+}
+
+impl IsoRED for TemplateListener {
+    #[inline]
+    fn type_name() -> &'static str {
+        "ref<TemplateListener>"
+    }
+}
+
+impl TemplateListener {
+    pub fn on_template_changed(&self, name: &str) {
+        call!(self.repr.clone(), "OnTemplateChanged" (name) -> ())
+    }
+}
+
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct RenderResult {
+    pub(crate) repr: Ref<RED4ext::IScriptable>,
+}
+
+impl IsoRED for RenderResult {
+    #[inline]
+    fn type_name() -> &'static str {
+        "ref<RenderResult>"
+    }
+}
+
+impl RenderResult {
+    pub fn new(widget: &Widget) -> Self {
+        let res = call!("Flexy.UI.RenderResult::New;" () -> RenderResult);
+        call!(res.repr.clone(), "SetWidget" (widget.clone()) -> ());
+        res
+    }
+
+    pub fn add(&self, id: &str, widget: &Widget, rect: [f32; 4]) {
+        let pos = Vector2::new(rect[0], rect[1]);
+        let size = Vector2::new(rect[2], rect[3]);
+        call!(self.repr.clone(), "Add" (id, widget.clone(), pos, size) -> ())
+    }
+}
+
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct LayoutRect {
+    pub(crate) repr: Ref<RED4ext::IScriptable>,
+}
+
+impl IsoRED for LayoutRect {
+    #[inline]
+    fn type_name() -> &'static str {
+        "ref<LayoutRect>"
+    }
+}
+
+impl LayoutRect {
+    pub fn from_computed(computed: &layout::ComputedLayout<Elem>) -> Self {
+        let res = call!("Flexy.UI.LayoutRect::New;" () -> LayoutRect);
+        let (rect, content) = (computed.rect, computed.content);
+        let data = vec![
+            rect.left,
+            rect.top,
+            rect.width,
+            rect.height,
+            content.left,
+            content.top,
+            content.width,
+            content.height,
+        ];
+        call!(res.repr.clone(), "SetData" (computed.elem.clone(), data) -> ());
+        for child in &computed.children {
+            call!(res.repr.clone(), "AddChild" (LayoutRect::from_computed(child)) -> ());
+        }
+        res
+    }
+}
+
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct Widget {
+    pub(crate) repr: Ref<RED4ext::IScriptable>,
+}
+
+impl IsoRED for Widget {
+    #[inline]
+    fn type_name() -> &'static str {
+        "ref<inkWidget>"
     }
 }
 
@@ -0,0 +1,1104 @@
+//! Render trees kept alive between frames, they own the layout state of the elements
+//! and the widgets spawned for them so that a relayout only touches what has changed.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use flexlayout_rs::{Dimension, Node, NodeWithLayout};
+use red4ext_rs::interop::Vector2;
+use red4ext_rs::prelude::*;
+
+use crate::layout::{self, LayoutElem};
+use crate::redscript::{self, Elem, Widget};
+use crate::tree;
+
+thread_local! {
+    static SESSIONS: RefCell<Sessions> = RefCell::new(Sessions::default());
+}
+
+#[derive(Default)]
+struct Sessions {
+    next_id: u64,
+    trees: HashMap<u64, RenderTree<Elem>>,
+    // elements marked while a tree was taken out for an update
+    updating: bool,
+    pending: Vec<usize>,
+}
+
+pub fn create(root: Elem, size: Vector2) -> u64 {
+    let mut tree = RenderTree::new(root);
+    tree.update(size);
+    report_duplicates(&mut tree);
+
+    SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        sessions.next_id += 1;
+        let id = sessions.next_id;
+        sessions.trees.insert(id, tree);
+        id
+    })
+}
+
+pub fn widget(id: u64) -> Widget {
+    SESSIONS.with(|sessions| {
+        let sessions = sessions.borrow();
+        sessions
+            .trees
+            .get(&id)
+            .and_then(|tree| tree.nodes.get(&tree.root))
+            .and_then(|node| node.widget.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Returns the widget of the first element with the id in tree order, like `RenderResult.IndexOf`.
+pub fn widget_by_id(id: u64, elem_id: &str) -> Widget {
+    SESSIONS.with(|sessions| {
+        let sessions = sessions.borrow();
+        sessions
+            .trees
+            .get(&id)
+            .and_then(|tree| tree.widget_by_id(tree.root, elem_id))
+            .unwrap_or_default()
+    })
+}
+
+pub fn relayout(id: u64, size: Vector2) {
+    // the tree is taken out for the duration of the update,
+    // the script calls made during it can mark elements dirty again
+    let tree = SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        sessions.updating = true;
+        sessions.trees.remove(&id)
+    });
+    if let Some(mut tree) = tree {
+        tree.update(size);
+        report_duplicates(&mut tree);
+
+        SESSIONS.with(|sessions| {
+            let mut sessions = sessions.borrow_mut();
+            for addr in std::mem::take(&mut sessions.pending) {
+                tree.mark_dirty(addr);
+            }
+            sessions.trees.insert(id, tree);
+        });
+    }
+    SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        sessions.updating = false;
+        sessions.pending.clear();
+    });
+}
+
+pub fn release(id: u64) {
+    let tree = SESSIONS.with(|sessions| sessions.borrow_mut().trees.remove(&id));
+    if let Some(mut tree) = tree {
+        let root = tree.root;
+        tree.remove(root);
+    }
+}
+
+fn report_duplicates(tree: &mut RenderTree<Elem>) {
+    for elem in tree.duplicates.drain(..) {
+        let id = elem.id().map(|id| format!(" (id '{}')", id)).unwrap_or_default();
+        redscript::log(&format!(
+            "[Flexy] An element can only be in a render tree once, a duplicate{} was left out",
+            id
+        ));
+    }
+}
+
+/// Marks a mounted element dirty, elements keep count of the trees they're mounted in
+/// and don't call this when they aren't mounted in any.
+pub fn mark_dirty(elem: &Elem) {
+    let addr = elem.addr();
+    SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        let mut found = false;
+        for tree in sessions.trees.values_mut() {
+            found |= tree.mark_dirty(addr);
+        }
+        if !found && sessions.updating {
+            sessions.pending.push(addr);
+        }
+    });
+}
+
+/// An element that can be kept in a render tree together with its widget.
+pub trait RenderElem: LayoutElem + Clone {
+    type Widget: Clone;
+
+    /// Returns a key that identifies the element for as long as it's alive.
+    fn addr(&self) -> usize;
+    fn id(&self) -> Option<String>;
+    /// Creates the widget of the element, with its appearance and handlers applied.
+    fn render(&self, pos: Vector2, size: Vector2) -> Self::Widget;
+    /// Updates the widget of the element after it has moved or has been marked dirty.
+    fn apply(&self, widget: &Self::Widget, pos: Vector2, size: Vector2);
+    /// Adds the widget of a child at the index among the widgets of its siblings.
+    fn insert_child(&self, widget: &Self::Widget, child: &Self::Widget, index: usize);
+    fn detach_child(&self, widget: &Self::Widget, child: &Self::Widget);
+    fn set_content_size(&self, size: Vector2);
+    /// Called when the element is added to or removed from a tree.
+    fn set_mounted(&self, mounted: bool);
+    fn hide(widget: &Self::Widget);
+}
+
+impl RenderElem for Elem {
+    type Widget = Widget;
+
+    #[inline]
+    fn addr(&self) -> usize {
+        Elem::addr(self)
+    }
+
+    #[inline]
+    fn id(&self) -> Option<String> {
+        Elem::id(self)
+    }
+
+    fn render(&self, pos: Vector2, size: Vector2) -> Widget {
+        let widget = call!(self.repr.clone(), "Render" (pos, size) -> Widget);
+        self.apply_appearance(&widget);
+        self.bind_handlers(&widget, true);
+        widget
+    }
+
+    fn apply(&self, widget: &Widget, pos: Vector2, size: Vector2) {
+        call!(self.repr.clone(), "Apply" (widget.clone(), pos, size) -> ());
+        self.apply_appearance(widget);
+        self.bind_handlers(widget, false);
+    }
+
+    #[inline]
+    fn insert_child(&self, widget: &Widget, child: &Widget, index: usize) {
+        Elem::insert_child(self, widget, child, index)
+    }
+
+    #[inline]
+    fn detach_child(&self, widget: &Widget, child: &Widget) {
+        Elem::detach_child(self, widget, child)
+    }
+
+    #[inline]
+    fn set_content_size(&self, size: Vector2) {
+        Elem::set_content_size(self, size)
+    }
+
+    #[inline]
+    fn set_mounted(&self, mounted: bool) {
+        Elem::set_mounted(self, mounted)
+    }
+
+    fn hide(widget: &Widget) {
+        call!(widget.repr.clone(), "SetVisible" (false) -> ());
+    }
+}
+
+/// The elements of a tree are laid out in regions: the root and every element with a fixed width
+/// and height, see [RenderTree::is_boundary], lay out their descendants down to the next such element,
+/// which takes part in the layout of its parent as a leaf. A region is only laid out again when one of
+/// its elements is dirty or its root has changed size. flexlayout nodes can't be changed once built,
+/// so the nodes of a region are built again whenever it's laid out.
+struct RenderTree<E: RenderElem> {
+    root: usize,
+    next_idx: usize,
+    nodes: HashMap<usize, RenderNode<E>>,
+    index: HashMap<usize, usize>,
+    // the size the tree was last laid out at
+    size: Option<(Option<f32>, Option<f32>)>,
+    // elements left out for being in the tree already, they're reported after an update
+    duplicates: Vec<E>,
+}
+
+struct RenderNode<E: RenderElem> {
+    elem: E,
+    id: Option<String>,
+    widget: Option<E::Widget>,
+    layout: tree::Layout,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    measured: Option<(f32, f32)>,
+    measured_for: Option<Option<f32>>,
+    // the width a leaf was last laid out at and the height it wraps to at it
+    wrapped: Option<(f32, f32)>,
+    // the gap excess of the layout the node was last built with, see layout::gap_excess
+    gap_excess: (f32, f32),
+    rect: Option<[f32; 4]>,
+    content: Option<(f32, f32)>,
+    dirty: bool,
+    // set when the element was moved to another parent, its widget is inserted into the new one on apply
+    moved: bool,
+}
+
+impl<E: RenderElem> RenderTree<E> {
+    fn new(root: E) -> Self {
+        let mut tree = Self {
+            root: 0,
+            next_idx: 0,
+            nodes: HashMap::new(),
+            index: HashMap::new(),
+            size: None,
+            duplicates: vec![],
+        };
+        tree.root = tree.insert(root, None, &mut HashSet::new());
+        tree
+    }
+
+    /// Adds a new element to the tree, `placed` collects the nodes placed during the update.
+    fn insert(&mut self, elem: E, parent: Option<usize>, placed: &mut HashSet<usize>) -> usize {
+        let idx = self.next_idx;
+        self.next_idx += 1;
+
+        let children = elem.children();
+        self.index.insert(elem.addr(), idx);
+        placed.insert(idx);
+        elem.set_mounted(true);
+        self.nodes.insert(idx, RenderNode {
+            layout: elem.layout(),
+            id: elem.id(),
+            elem,
+            widget: None,
+            parent,
+            children: vec![],
+            measured: None,
+            measured_for: None,
+            wrapped: None,
+            gap_excess: (0f32, 0f32),
+            rect: None,
+            content: None,
+            dirty: false,
+            moved: false,
+        });
+        let children = children
+            .into_iter()
+            .filter_map(|child| self.place(child, idx, placed))
+            .collect();
+        if let Some(node) = self.nodes.get_mut(&idx) {
+            node.children = children;
+        }
+        idx
+    }
+
+    /// Returns the node of a child of the element. A child that's already in the tree is moved
+    /// from its previous parent, unless it's still a child of it, it has already been placed
+    /// during the update or it's an ancestor of the element. It's a duplicate then and it's left out.
+    fn place(&mut self, elem: E, parent: usize, placed: &mut HashSet<usize>) -> Option<usize> {
+        let idx = match self.index.get(&elem.addr()).copied() {
+            Some(idx) => idx,
+            None => return Some(self.insert(elem, Some(parent), placed)),
+        };
+        let previous = self.nodes.get(&idx).and_then(|node| node.parent);
+        let is_duplicate = placed.contains(&idx)
+            || self.is_ancestor(idx, parent)
+            || previous.is_some_and(|previous| previous != parent && self.lists(previous, &elem));
+        if is_duplicate {
+            self.duplicates.push(elem);
+            return None;
+        }
+        placed.insert(idx);
+        if let Some(previous) = previous.filter(|previous| *previous != parent) {
+            self.detach(previous, idx);
+            if let Some(node) = self.nodes.get_mut(&idx) {
+                node.parent = Some(parent);
+                node.moved = node.widget.is_some();
+            }
+        }
+        Some(idx)
+    }
+
+    /// Takes a child away from its parent, together with its widget.
+    fn detach(&mut self, parent: usize, child: usize) {
+        let widget = self.nodes.get(&child).and_then(|node| node.widget.clone());
+        if let Some(parent) = self.nodes.get_mut(&parent) {
+            parent.children.retain(|other| *other != child);
+            parent.dirty = true;
+            if let (Some(parent_widget), Some(widget)) = (&parent.widget, &widget) {
+                parent.elem.detach_child(parent_widget, widget);
+            }
+        }
+    }
+
+    fn is_ancestor(&self, idx: usize, of: usize) -> bool {
+        let mut current = Some(of);
+        while let Some(node) = current {
+            if node == idx {
+                return true;
+            }
+            current = self.nodes.get(&node).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    /// Returns whether the element of a node still has the child.
+    fn lists(&self, idx: usize, child: &E) -> bool {
+        self.nodes.get(&idx).is_some_and(|node| {
+            node.elem
+                .children()
+                .iter()
+                .any(|other| other.addr() == child.addr())
+        })
+    }
+
+    fn remove(&mut self, idx: usize) {
+        if let Some(node) = self.nodes.remove(&idx) {
+            self.index.remove(&node.elem.addr());
+            node.elem.set_mounted(false);
+            for child in node.children {
+                self.remove(child);
+            }
+        }
+    }
+
+    /// Searches the displayed elements under the node depth-first, in the order they're rendered.
+    fn widget_by_id(&self, idx: usize, elem_id: &str) -> Option<E::Widget> {
+        let node = self.nodes.get(&idx).filter(|_| self.is_displayed(idx))?;
+        if node.id.as_deref() == Some(elem_id) {
+            if let Some(widget) = &node.widget {
+                return Some(widget.clone());
+            }
+        }
+        node.children
+            .iter()
+            .find_map(|child| self.widget_by_id(*child, elem_id))
+    }
+
+    fn mark_dirty(&mut self, addr: usize) -> bool {
+        match self.index.get(&addr).and_then(|idx| self.nodes.get_mut(idx)) {
+            Some(node) => {
+                node.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update(&mut self, size: Vector2) {
+        let width = if size.x > 0f32 { Some(size.x) } else { None };
+        let height = if size.y > 0f32 { Some(size.y) } else { None };
+
+        let dirty: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.dirty)
+            .map(|(idx, _)| *idx)
+            .collect();
+        // children are removed once every element is refreshed, they may have been moved to another one
+        let mut placed = HashSet::new();
+        let mut dropped = vec![];
+        for idx in dirty {
+            self.refresh(idx, &mut placed, &mut dropped);
+        }
+        for (parent, child) in dropped {
+            if self
+                .nodes
+                .get(&child)
+                .is_some_and(|node| node.parent == Some(parent))
+            {
+                self.detach(parent, child);
+                self.remove(child);
+            }
+        }
+
+        let stale = self.stale_regions();
+        if self.size != Some((width, height)) || stale.contains(&self.root) {
+            self.size = Some((width, height));
+            let res = self.lay_out_region(self.root, width, height, None);
+            self.apply(res, None, &stale);
+        } else if !stale.is_empty() {
+            self.apply_stale_regions(self.root, &stale);
+        }
+    }
+
+    /// Returns the roots of the regions with dirty elements, a dirty region root also makes
+    /// the region of its parent stale since its size may have changed.
+    fn stale_regions(&self) -> HashSet<usize> {
+        let mut stale = HashSet::new();
+        for (idx, node) in &self.nodes {
+            if !node.dirty {
+                continue;
+            }
+            if let Some(region) = self.region_of(*idx) {
+                stale.insert(region);
+                if self.is_boundary(*idx) {
+                    stale.insert(*idx);
+                }
+            }
+        }
+        stale
+    }
+
+    /// Returns the root of the region the element is laid out in,
+    /// or none when an ancestor leaves it out of the layout.
+    fn region_of(&self, idx: usize) -> Option<usize> {
+        let mut region = None;
+        let mut current = self.nodes.get(&idx)?.parent;
+        while let Some(parent) = current {
+            if !self.is_displayed(parent) {
+                return None;
+            }
+            if region.is_none() && self.is_boundary(parent) {
+                region = Some(parent);
+            }
+            current = self.nodes.get(&parent)?.parent;
+        }
+        Some(region.unwrap_or(self.root))
+    }
+
+    /// Returns whether an element is the root of a region, its size doesn't depend
+    /// on its children, so they can be laid out without the rest of the tree.
+    fn is_boundary(&self, idx: usize) -> bool {
+        idx != self.root
+            && self.nodes.get(&idx).is_some_and(|node| {
+                !node.children.is_empty()
+                    && matches!(node.layout.width, Dimension::Point(_))
+                    && matches!(node.layout.height, Dimension::Point(_))
+            })
+    }
+
+    /// Lays out the elements of a region, the size is the one its root was given in the layout
+    /// of its parent, the root of the tree is laid out within the available width and height instead.
+    /// Leaves are laid out again with the heights they wrap to, see layout::lay_out.
+    fn lay_out_region(
+        &mut self,
+        idx: usize,
+        width: Option<f32>,
+        height: Option<f32>,
+        size: Option<(f32, f32)>,
+    ) -> NodeWithLayout<usize> {
+        let mut pass = 1;
+        loop {
+            let node = self.build(idx, width, None, tree::Edges::default(), Some(size));
+            let res = node.layout(width, height);
+            if pass == layout::MAX_PASSES || !self.remeasure(&res) {
+                return res;
+            }
+            pass += 1;
+        }
+    }
+
+    /// Reads the layout and the children of a dirty element again,
+    /// children that are no longer present are added to `dropped` together with the element.
+    fn refresh(&mut self, idx: usize, placed: &mut HashSet<usize>, dropped: &mut Vec<(usize, usize)>) {
+        let (elem, old_children) = match self.nodes.get(&idx) {
+            Some(node) => (node.elem.clone(), node.children.clone()),
+            None => return,
+        };
+        let layout = elem.layout();
+        let elem_id = elem.id();
+
+        let children: Vec<usize> = elem
+            .children()
+            .into_iter()
+            .filter_map(|child| self.place(child, idx, placed))
+            .collect();
+        dropped.extend(
+            old_children
+                .into_iter()
+                .filter(|old| !children.contains(old))
+                .map(|old| (idx, old)),
+        );
+
+        if let Some(node) = self.nodes.get_mut(&idx) {
+            node.layout = layout;
+            node.id = elem_id;
+            node.children = children;
+            node.measured_for = None;
+        }
+    }
+
+    /// Builds the nodes of a region, `region` is set for its root together with the size it has.
+    fn build(
+        &mut self,
+        idx: usize,
+        available_width: Option<f32>,
+        parent: Option<&tree::Layout>,
+        spacing: tree::Edges,
+        region: Option<Option<(f32, f32)>>,
+    ) -> Node<usize> {
+        let is_boundary = region.is_none() && self.is_boundary(idx);
+        let node = self.nodes.get_mut(&idx).expect("missing render node");
+        let mut layout = node.layout.clone();
+        layout::apply_overflow(&mut layout, parent);
+        layout::resolve_percentages(&mut layout, parent, available_width);
+        if let Some(Some((width, height))) = region {
+            layout.width = Dimension::Point(width);
+            layout.height = Dimension::Point(height);
+        }
+        let inner_width = layout::inner_width(&layout, spacing, available_width);
+        node.gap_excess = layout::gap_excess(&layout);
+        if is_boundary {
+            return Node::new(vec![], layout::flex_properties(&layout, spacing), idx);
+        }
+
+        if node.children.is_empty() {
+            if node.measured_for != Some(inner_width) {
+                node.measured = LayoutElem::measure(&node.elem, inner_width);
+                node.measured_for = Some(inner_width);
+                node.wrapped = None;
+            }
+            let wrapped = node.wrapped.map(|(_, height)| height);
+            let measured = node
+                .measured
+                .map(|(width, height)| (width, wrapped.unwrap_or(height)));
+            layout::apply_intrinsic_size(&mut layout, measured, parent);
+        }
+
+        let children: Vec<usize> = node.children.clone();
+        let children: Vec<usize> = children
+            .into_iter()
+            .filter(|child| self.is_displayed(*child))
+            .collect();
+        let child_layouts: Vec<_> = children.iter().map(|child| &self.nodes[child].layout).collect();
+        let gaps = layout::gap_margins(&layout, &child_layouts);
+        let children = children
+            .into_iter()
+            .zip(gaps)
+            .map(|(child, gap)| self.build(child, inner_width, Some(&layout), gap, None))
+            .collect();
+        Node::new(children, layout::flex_properties(&layout, spacing), idx)
+    }
+
+    /// Measures the leaves again at the widths they were laid out at unless they already were,
+    /// returns whether any of them wraps to a different height than it was laid out with.
+    fn remeasure(&mut self, res: &NodeWithLayout<usize>) -> bool {
+        let children: Vec<_> = res.children().into_iter().collect();
+        if !children.is_empty() {
+            return children
+                .iter()
+                .fold(false, |changed, child| self.remeasure(child) || changed);
+        }
+        let [_, _, width, _] = self.rect(res);
+        let node = match self.nodes.get_mut(res.inner().context()) {
+            Some(node) if node.children.is_empty() => node,
+            _ => return false,
+        };
+        let measured_height = match node.measured {
+            Some((_, height)) => node.wrapped.map_or(height, |(_, wrapped)| wrapped),
+            None => return false,
+        };
+        let width = (width - layout::horizontal_inset(&node.layout)).max(0f32);
+        if node.wrapped.is_some_and(|(wrapped_for, _)| wrapped_for == width) {
+            return false;
+        }
+        let height =
+            LayoutElem::measure(&node.elem, Some(width)).map_or(measured_height, |(_, height)| height);
+        node.wrapped = Some((width, height));
+        layout::height_changed(measured_height, height)
+    }
+
+    /// Renders the elements that don't have a widget yet and updates the ones
+    /// that are dirty or have moved, widgets of the other elements are left untouched.
+    /// The regions nested in the layout are laid out and applied when they're stale or resized.
+    fn apply(&mut self, res: NodeWithLayout<usize>, parent: Option<usize>, stale: &HashSet<usize>) {
+        let idx = *res.inner().context();
+        let rect = self.rect(&res);
+        let pos = Vector2::new(rect[0], rect[1]);
+        let size = Vector2::new(rect[2], rect[3]);
+        let is_boundary = parent.is_some() && self.is_boundary(idx);
+        let parent = parent
+            .filter(|_| {
+                self.nodes
+                    .get(&idx)
+                    .is_some_and(|node| node.widget.is_none() || node.moved)
+            })
+            .and_then(|parent| self.attachment(parent, idx));
+
+        let node = self.nodes.get_mut(&idx).expect("missing render node");
+        let rendered = if let Some(widget) = node.widget.clone() {
+            if node.dirty || node.moved || node.rect != Some(rect) {
+                node.elem.apply(&widget, pos, size);
+            }
+            if let Some((parent, parent_widget, index)) = parent {
+                parent.insert_child(&parent_widget, &widget, index);
+            }
+            node.moved = false;
+            false
+        } else {
+            let widget = node.elem.render(pos, size);
+            if let Some((parent, parent_widget, index)) = parent {
+                parent.insert_child(&parent_widget, &widget, index);
+            }
+            node.widget = Some(widget);
+            true
+        };
+        let resized = node.rect.map(|[_, _, width, height]| (width, height)) != Some((rect[2], rect[3]));
+        node.rect = Some(rect);
+        node.dirty = false;
+        if is_boundary {
+            if resized || stale.contains(&idx) {
+                let res = self.lay_out_region(idx, None, None, Some((rect[2], rect[3])));
+                self.apply_region(res, stale, rendered);
+            } else {
+                self.apply_stale_regions(idx, stale);
+            }
+        } else {
+            self.apply_region(res, stale, rendered);
+        }
+    }
+
+    /// Applies the layout of the children of a region root, or of an element within a region.
+    fn apply_region(&mut self, res: NodeWithLayout<usize>, stale: &HashSet<usize>, rendered: bool) {
+        let idx = *res.inner().context();
+        let content = self.content_size(&res);
+        let node = self.nodes.get_mut(&idx).expect("missing render node");
+        if let Some((width, height)) = content {
+            if rendered || node.content != content {
+                node.elem.set_content_size(Vector2::new(width, height));
+            }
+        }
+        node.content = content;
+
+        // the widgets of children left out of the layout are hidden until they're displayed again,
+        // at which point they're marked dirty and applied
+        for child in node.children.clone() {
+            if !self.is_displayed(child) {
+                if let Some(child) = self.nodes.get_mut(&child) {
+                    child.dirty = false;
+                    if let Some(child_widget) = &child.widget {
+                        E::hide(child_widget);
+                    }
+                }
+            }
+        }
+        for child in res.children() {
+            self.apply(child, Some(idx), stale);
+        }
+    }
+
+    /// Lays out and applies the stale regions nested under an element whose region is up to date.
+    fn apply_stale_regions(&mut self, idx: usize, stale: &HashSet<usize>) {
+        let children = self.nodes.get(&idx).map_or(vec![], |node| node.children.clone());
+        for child in children {
+            if !self.is_displayed(child) {
+                continue;
+            }
+            let rect = self.nodes.get(&child).and_then(|node| node.rect);
+            match rect {
+                Some([_, _, width, height]) if self.is_boundary(child) && stale.contains(&child) => {
+                    let res = self.lay_out_region(child, None, None, Some((width, height)));
+                    self.apply_region(res, stale, false);
+                }
+                _ => self.apply_stale_regions(child, stale),
+            }
+        }
+    }
+
+    /// Returns the parent element and widget a new widget of the child is inserted into,
+    /// together with the number of widgets of the children before it.
+    fn attachment(&self, parent: usize, child: usize) -> Option<(E, E::Widget, usize)> {
+        let node = self.nodes.get(&parent)?;
+        let widget = node.widget.clone()?;
+        let index = node
+            .children
+            .iter()
+            .take_while(|sibling| **sibling != child)
+            .filter(|sibling| self.nodes.get(sibling).is_some_and(|n| n.widget.is_some()))
+            .count();
+        Some((node.elem.clone(), widget, index))
+    }
+
+    /// Returns the size of the content of an element that clips its children.
+    fn content_size(&self, res: &NodeWithLayout<usize>) -> Option<(f32, f32)> {
+        let layout = &self.nodes.get(res.inner().context())?.layout;
+        if layout.overflow == tree::Overflow::Visible {
+            return None;
+        }
+        let children = res.children().into_iter().filter_map(|child| {
+            let margin = self.nodes.get(child.inner().context())?.layout.margin;
+            Some((self.rect(&child), margin))
+        });
+        let [_, _, width, height] = self.rect(res);
+        Some(layout::content_size(layout, (width, height), children))
+    }
+
+    /// Returns the rect of a laid out node, see layout::gap_excess.
+    fn rect(&self, res: &NodeWithLayout<usize>) -> [f32; 4] {
+        let (excess_width, excess_height) = self
+            .nodes
+            .get(res.inner().context())
+            .map_or((0f32, 0f32), |node| node.gap_excess);
+        [
+            res.left(),
+            res.top(),
+            res.width() - excess_width,
+            res.height() - excess_height,
+        ]
+    }
+
+    fn is_displayed(&self, idx: usize) -> bool {
+        self.nodes
+            .get(&idx)
+            .is_some_and(|node| node.layout.display != tree::Display::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use flexlayout_rs::Dimension;
+    use red4ext_rs::interop::Vector2;
+
+    use super::{RenderElem, RenderTree};
+    use crate::layout::LayoutElem;
+    use crate::{markup, tree};
+
+    /// A markup element whose layout and children can be changed after it's mounted.
+    #[derive(Clone)]
+    struct TestElem(Rc<TestNode>);
+
+    struct TestNode {
+        elem: RefCell<tree::Elem>,
+        children: RefCell<Vec<TestElem>>,
+        renders: Cell<usize>,
+        applies: Cell<usize>,
+        mounts: Cell<i32>,
+    }
+
+    #[derive(Clone)]
+    struct TestWidget(Rc<WidgetState>);
+
+    struct WidgetState {
+        id: Option<String>,
+        rect: Cell<[f32; 4]>,
+        visible: Cell<bool>,
+        children: RefCell<Vec<TestWidget>>,
+    }
+
+    impl TestElem {
+        fn parse(str: &str) -> anyhow::Result<Self> {
+            Ok(Self::from_tree(&markup::parse(str)?))
+        }
+
+        fn from_tree(elem: &tree::Elem) -> Self {
+            let children = elem.children.iter().map(Self::from_tree).collect();
+            let mut elem = elem.clone();
+            elem.children.clear();
+            Self(Rc::new(TestNode {
+                elem: RefCell::new(elem),
+                children: RefCell::new(children),
+                renders: Cell::new(0),
+                applies: Cell::new(0),
+                mounts: Cell::new(0),
+            }))
+        }
+
+        fn child(&self, idx: usize) -> TestElem {
+            self.0.children.borrow()[idx].clone()
+        }
+    }
+
+    impl TestWidget {
+        fn child_ids(&self) -> Vec<Option<String>> {
+            self.0
+                .children
+                .borrow()
+                .iter()
+                .map(|child| child.0.id.clone())
+                .collect()
+        }
+    }
+
+    impl LayoutElem for TestElem {
+        fn children(&self) -> Vec<Self> {
+            self.0.children.borrow().clone()
+        }
+
+        fn layout(&self) -> tree::Layout {
+            self.0.elem.borrow().layout.clone()
+        }
+
+        fn measure(&self, max_width: Option<f32>) -> Option<(f32, f32)> {
+            (&*self.0.elem.borrow()).measure(max_width)
+        }
+    }
+
+    impl RenderElem for TestElem {
+        type Widget = TestWidget;
+
+        fn addr(&self) -> usize {
+            Rc::as_ptr(&self.0) as usize
+        }
+
+        fn id(&self) -> Option<String> {
+            self.0.elem.borrow().id.clone()
+        }
+
+        fn render(&self, pos: Vector2, size: Vector2) -> TestWidget {
+            self.0.renders.set(self.0.renders.get() + 1);
+            TestWidget(Rc::new(WidgetState {
+                id: self.id(),
+                rect: Cell::new([pos.x, pos.y, size.x, size.y]),
+                visible: Cell::new(true),
+                children: RefCell::new(vec![]),
+            }))
+        }
+
+        fn apply(&self, widget: &TestWidget, pos: Vector2, size: Vector2) {
+            self.0.applies.set(self.0.applies.get() + 1);
+            widget.0.rect.set([pos.x, pos.y, size.x, size.y]);
+            widget.0.visible.set(true);
+        }
+
+        fn insert_child(&self, widget: &TestWidget, child: &TestWidget, index: usize) {
+            widget.0.children.borrow_mut().insert(index, child.clone());
+        }
+
+        fn detach_child(&self, widget: &TestWidget, child: &TestWidget) {
+            widget
+                .0
+                .children
+                .borrow_mut()
+                .retain(|other| !Rc::ptr_eq(&other.0, &child.0));
+        }
+
+        fn set_content_size(&self, _size: Vector2) {}
+
+        fn set_mounted(&self, mounted: bool) {
+            self.0
+                .mounts
+                .set(self.0.mounts.get() + if mounted { 1 } else { -1 });
+        }
+
+        fn hide(widget: &TestWidget) {
+            widget.0.visible.set(false);
+        }
+    }
+
+    fn root_widget(tree: &RenderTree<TestElem>) -> TestWidget {
+        tree.nodes[&tree.root].widget.clone().unwrap()
+    }
+
+    #[test]
+    fn it_applies_only_dirty_and_moved_elements() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='100pt' height='20pt'>\
+               <box id='a' width='30pt'></box>\
+               <box id='b' width='30pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let widget = root_widget(&tree);
+        assert_eq!(widget.child_ids(), [Some("a".to_owned()), Some("b".to_owned())]);
+        assert_eq!(widget.0.children.borrow()[1].0.rect.get(), [
+            30.0, 0.0, 30.0, 20.0
+        ]);
+
+        tree.update(Vector2::new(0.0, 0.0));
+        assert_eq!(root.child(0).0.applies.get(), 0);
+
+        let (a, b) = (root.child(0), root.child(1));
+        a.0.elem.borrow_mut().layout.width = Dimension::Point(50.0);
+        assert!(tree.mark_dirty(a.addr()));
+        tree.update(Vector2::new(0.0, 0.0));
+
+        assert_eq!(a.0.applies.get(), 1);
+        assert_eq!(b.0.applies.get(), 1);
+        assert_eq!(root.0.applies.get(), 0);
+        assert_eq!((a.0.renders.get(), b.0.renders.get()), (1, 1));
+        assert_eq!(widget.0.children.borrow()[1].0.rect.get(), [
+            50.0, 0.0, 30.0, 20.0
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_relayouts_on_resize() -> anyhow::Result<()> {
+        let root = TestElem::parse("<box><box width='50%' height='10pt'></box></box>")?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(100.0, 20.0));
+        tree.update(Vector2::new(200.0, 20.0));
+
+        let child = root_widget(&tree).0.children.borrow()[0].clone();
+        assert_eq!(child.0.rect.get(), [0.0, 0.0, 100.0, 10.0]);
+        assert_eq!(root.child(0).0.renders.get(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn it_inserts_and_removes_children() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='100pt' height='20pt'>\
+               <box id='a' width='30pt'></box>\
+               <box id='b' width='30pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let removed = root.child(0);
+        let added = TestElem::parse("<box id='c' width='40pt'></box>")?;
+        root.0.children.borrow_mut().remove(0);
+        root.0.children.borrow_mut().insert(0, added.clone());
+        assert!(tree.mark_dirty(root.addr()));
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let widget = root_widget(&tree);
+        assert_eq!(widget.child_ids(), [Some("c".to_owned()), Some("b".to_owned())]);
+        assert_eq!(widget.0.children.borrow()[1].0.rect.get(), [
+            40.0, 0.0, 30.0, 20.0
+        ]);
+        assert!(!tree.mark_dirty(removed.addr()));
+        assert!(tree.mark_dirty(added.addr()));
+        assert_eq!((removed.0.mounts.get(), added.0.mounts.get()), (0, 1));
+
+        let root_idx = tree.root;
+        tree.remove(root_idx);
+        assert_eq!((root.0.mounts.get(), added.0.mounts.get()), (0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn it_finds_the_first_widget_with_an_id() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box>\
+               <box display='none' id='a'></box>\
+               <box><box id='a' width='10pt'></box></box>\
+               <box id='a' width='20pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root);
+        tree.update(Vector2::new(100.0, 20.0));
+
+        let widget = tree.widget_by_id(tree.root, "a").unwrap();
+        assert_eq!(widget.0.rect.get()[2], 10.0);
+        assert!(tree.widget_by_id(tree.root, "b").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn it_hides_elements_left_out_of_the_layout() -> anyhow::Result<()> {
+        let root = TestElem::parse("<box><box id='a' width='30pt'></box></box>")?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(100.0, 20.0));
+
+        let child = root.child(0);
+        child.0.elem.borrow_mut().layout.display = tree::Display::None;
+        tree.mark_dirty(child.addr());
+        tree.update(Vector2::new(100.0, 20.0));
+        assert!(!root_widget(&tree).0.children.borrow()[0].0.visible.get());
+        Ok(())
+    }
+
+    #[test]
+    fn it_lays_out_fixed_size_elements_on_their_own() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='300pt' height='100pt' align-items='start'>\
+               <box width='100pt' height='50pt'>\
+                 <box id='a' width='30pt'></box>\
+                 <box id='b' width='30pt'></box>\
+               </box>\
+               <box id='c' width='50pt' height='10pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let (panel, c) = (root.child(0), root.child(1));
+        let a = panel.child(0);
+        a.0.elem.borrow_mut().layout.width = Dimension::Point(60.0);
+        assert!(tree.mark_dirty(a.addr()));
+        assert_eq!(tree.stale_regions().into_iter().collect::<Vec<_>>(), [
+            tree.index[&panel.addr()]
+        ]);
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let panel_widget = root_widget(&tree).0.children.borrow()[0].clone();
+        let children = panel_widget.0.children.borrow();
+        assert_eq!(children[0].0.rect.get(), [0.0, 0.0, 60.0, 50.0]);
+        assert_eq!(children[1].0.rect.get(), [60.0, 0.0, 30.0, 50.0]);
+        assert_eq!((panel.0.applies.get(), c.0.applies.get()), (0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn it_lays_out_fixed_size_elements_again_when_resized() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='150pt' height='50pt'>\
+               <box width='100pt' height='50pt'><box flex-grow='1'></box></box>\
+               <box width='100pt' height='50pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let panel_widget = root_widget(&tree).0.children.borrow()[0].clone();
+        let grown = panel_widget.0.children.borrow()[0].clone();
+        assert_eq!(grown.0.rect.get(), [0.0, 0.0, 75.0, 50.0]);
+
+        let sibling = root.child(1);
+        sibling.0.elem.borrow_mut().layout.width = Dimension::Point(50.0);
+        assert!(tree.mark_dirty(sibling.addr()));
+        tree.update(Vector2::new(0.0, 0.0));
+        assert_eq!(panel_widget.0.rect.get(), [0.0, 0.0, 100.0, 50.0]);
+        assert_eq!(grown.0.rect.get(), [0.0, 0.0, 100.0, 50.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_moves_reparented_elements() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='100pt' height='20pt'>\
+               <box id='p' width='50pt'><box id='x' width='10pt'></box></box>\
+               <box id='q' width='50pt'></box>\
+             </box>",
+        )?;
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let (p, q) = (root.child(0), root.child(1));
+        let x = p.0.children.borrow_mut().remove(0);
+        q.0.children.borrow_mut().push(x.clone());
+        assert!(tree.mark_dirty(p.addr()));
+        assert!(tree.mark_dirty(q.addr()));
+        tree.update(Vector2::new(0.0, 0.0));
+
+        let widget = root_widget(&tree);
+        let (p_widget, q_widget) = {
+            let children = widget.0.children.borrow();
+            (children[0].clone(), children[1].clone())
+        };
+        assert!(p_widget.child_ids().is_empty());
+        assert_eq!(q_widget.child_ids(), [Some("x".to_owned())]);
+        assert_eq!(q_widget.0.children.borrow()[0].0.rect.get(), [
+            0.0, 0.0, 10.0, 20.0
+        ]);
+        assert_eq!((x.0.mounts.get(), x.0.renders.get()), (1, 1));
+        assert!(tree.duplicates.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn it_leaves_out_duplicate_elements() -> anyhow::Result<()> {
+        let root = TestElem::parse(
+            "<box width='100pt' height='20pt'>\
+               <box id='p' width='50pt'><box id='x' width='10pt'></box></box>\
+               <box id='q' width='50pt'></box>\
+             </box>",
+        )?;
+        let (p, q) = (root.child(0), root.child(1));
+        let x = p.child(0);
+        p.0.children.borrow_mut().push(x.clone());
+        let mut tree = RenderTree::new(root.clone());
+        tree.update(Vector2::new(0.0, 0.0));
+        assert_eq!(tree.duplicates.len(), 1);
+        assert_eq!(x.0.mounts.get(), 1);
+
+        tree.duplicates.clear();
+        p.0.children.borrow_mut().pop();
+        q.0.children.borrow_mut().push(x.clone());
+        assert!(tree.mark_dirty(q.addr()));
+        tree.update(Vector2::new(0.0, 0.0));
+
+        assert_eq!(tree.duplicates.len(), 1);
+        let widget = root_widget(&tree);
+        let children = widget.0.children.borrow();
+        assert_eq!(children[0].child_ids(), [Some("x".to_owned())]);
+        assert!(children[1].child_ids().is_empty());
+        assert_eq!(x.0.mounts.get(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Outlines of the shapes drawn with `inkShape`, which renders a list of vertices.
+
+/// The number of segments each rounded corner is approximated with.
+const CORNER_SEGMENTS: usize = 8;
+
+/// Returns the outline of a rectangle with rounded corners, going clockwise from the top left corner.
+/// The outline is inset by the given amount on each side so that a border line drawn along it
+/// stays within the rectangle, the radius is clamped to half of the shorter side.
+pub fn rounded_rect(width: f32, height: f32, radius: f32, inset: f32) -> Vec<(f32, f32)> {
+    let inner_width = (width - 2f32 * inset).max(0f32);
+    let inner_height = (height - 2f32 * inset).max(0f32);
+    let radius = (radius - inset).clamp(0f32, inner_width.min(inner_height) / 2f32);
+    let (left, top) = (inset + radius, inset + radius);
+    let (right, bottom) = (inset + inner_width - radius, inset + inner_height - radius);
+
+    if radius == 0f32 {
+        return vec![(left, top), (right, top), (right, bottom), (left, bottom)];
+    }
+    let corners = [
+        (left, top, 180f32),
+        (right, top, 270f32),
+        (right, bottom, 0f32),
+        (left, bottom, 90f32),
+    ];
+    corners
+        .iter()
+        .flat_map(|&(x, y, start)| {
+            (0..=CORNER_SEGMENTS).map(move |step| {
+                let angle = (start + 90f32 * step as f32 / CORNER_SEGMENTS as f32).to_radians();
+                (x + radius * angle.cos(), y + radius * angle.sin())
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rounded_rect, CORNER_SEGMENTS};
+
+    #[test]
+    fn it_outlines_rounded_rects() {
+        assert_eq!(rounded_rect(10.0, 20.0, 0.0, 1.0), vec![
+            (1.0, 1.0),
+            (9.0, 1.0),
+            (9.0, 19.0),
+            (1.0, 19.0)
+        ]);
+
+        let outline = rounded_rect(100.0, 40.0, 8.0, 2.0);
+        assert_eq!(outline.len(), 4 * (CORNER_SEGMENTS + 1));
+        let close = |(x, y): (f32, f32), (ex, ey): (f32, f32)| (x - ex).abs() < 1e-3 && (y - ey).abs() < 1e-3;
+        assert!(close(outline[0], (2.0, 8.0)));
+        assert!(close(outline[CORNER_SEGMENTS], (8.0, 2.0)));
+        assert!(close(outline[CORNER_SEGMENTS + 1], (92.0, 2.0)));
+        assert!(outline
+            .iter()
+            .all(|&(x, y)| (1.999..=98.001).contains(&x) && (1.999..=38.001).contains(&y)));
+
+        let outline = rounded_rect(10.0, 10.0, 50.0, 0.0);
+        assert!(close(outline[0], (0.0, 5.0)));
+    }
+}
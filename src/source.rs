@@ -0,0 +1,172 @@
+//! Lookup of the templates referenced by name, from `LoadElem` or from `<include>` tags.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::markup::{Error, ErrorKind, Result};
+
+/// Provides the markup of templates and their stylesheets.
+pub trait Source {
+    /// Returns the markup of a template and its stylesheet if it has one.
+    fn read(&self, name: &str) -> Result<(String, Option<String>)>;
+
+    /// Returns the time the template or its stylesheet was last changed, if it's known.
+    fn modified(&self, _name: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Reads `<name>.html` and `<name>.css` from a directory. Names prefixed with a namespace
+/// like `mymod:hud/panel` are looked up in the root registered for the namespace instead.
+pub struct Files {
+    root: PathBuf,
+    namespaces: HashMap<String, PathBuf>,
+}
+
+impl Files {
+    pub fn add_root(&mut self, namespace: &str, path: impl Into<PathBuf>) -> Result<()> {
+        if !is_valid_segment(namespace) {
+            return Err(Error::new(ErrorKind::InvalidTemplateName(namespace.to_owned())));
+        }
+        self.namespaces.insert(namespace.to_owned(), path.into());
+        Ok(())
+    }
+
+    /// Resolves a template name to a path without an extension, names are made of plain
+    /// path segments separated with `/` so that they can't escape their root.
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        let (root, path) = match name.split_once(':') {
+            Some((namespace, path)) => {
+                let root = self
+                    .namespaces
+                    .get(namespace)
+                    .ok_or_else(|| Error::new(ErrorKind::UnknownNamespace(namespace.to_owned())))?;
+                (root, path)
+            }
+            None => (&self.root, name),
+        };
+        if !path.split('/').all(is_valid_segment) {
+            return Err(Error::new(ErrorKind::InvalidTemplateName(name.to_owned())));
+        }
+        Ok(path
+            .split('/')
+            .fold(root.clone(), |acc, segment| acc.join(segment)))
+    }
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("r6").join("ui").join("templates"),
+            namespaces: HashMap::new(),
+        }
+    }
+}
+
+impl Source for Files {
+    fn read(&self, name: &str) -> Result<(String, Option<String>)> {
+        let path = self.path(name)?;
+
+        let markup = std::fs::read_to_string(path.with_extension("html"))
+            .map_err(|err| Error::new(ErrorKind::Io(err)))?;
+        let style = match std::fs::read_to_string(path.with_extension("css")) {
+            Ok(css) => Some(css),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(Error::new(ErrorKind::Io(err))),
+        };
+        Ok((markup, style))
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        let path = self.path(name).ok()?;
+        ["html", "css"]
+            .iter()
+            .filter_map(|ext| std::fs::metadata(path.with_extension(ext)).ok()?.modified().ok())
+            .max()
+    }
+}
+
+/// Qualifies the name of an included template with the namespace of the including one,
+/// names that already have a namespace are left as they are.
+pub fn qualify<'a>(name: &'a str, parent: Option<&str>) -> Cow<'a, str> {
+    let namespace = parent.and_then(|parent| parent.split_once(':')).map(|(ns, _)| ns);
+    match namespace {
+        Some(namespace) if !name.contains(':') => Cow::Owned(format!("{}:{}", namespace, name)),
+        _ => Cow::Borrowed(name),
+    }
+}
+
+fn is_valid_segment(str: &str) -> bool {
+    !str.is_empty()
+        && !str.starts_with('.')
+        && str
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+}
+
+/// Templates kept in memory, mostly useful for tests.
+impl Source for HashMap<&str, &str> {
+    fn read(&self, name: &str) -> Result<(String, Option<String>)> {
+        match self.get(name) {
+            Some(markup) => Ok((markup.to_string(), None)),
+            None => Err(Error::new(ErrorKind::Io(std::io::ErrorKind::NotFound.into()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{qualify, Files};
+    use crate::markup::ErrorKind;
+
+    #[test]
+    fn it_resolves_namespaced_names() -> anyhow::Result<()> {
+        let mut files = Files::default();
+        files.add_root("mymod", "mods/mymod/templates")?;
+        let root = PathBuf::from("r6").join("ui").join("templates");
+        assert_eq!(files.path("hud")?, root.join("hud"));
+        assert_eq!(
+            files.path("mymod:hud/panel")?,
+            PathBuf::from("mods/mymod/templates").join("hud").join("panel")
+        );
+        assert!(matches!(
+            files.path("other:hud").unwrap_err().kind,
+            ErrorKind::UnknownNamespace(ns) if ns == "other"
+        ));
+        assert!(files.add_root("my/mod", "mods").is_err());
+
+        assert_eq!(
+            qualify("widgets/bar", Some("mymod:hud/panel")),
+            "mymod:widgets/bar"
+        );
+        assert_eq!(qualify("base:widgets/bar", Some("mymod:hud")), "base:widgets/bar");
+        assert_eq!(qualify("widgets/bar", Some("hud")), "widgets/bar");
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_names_escaping_the_root() {
+        let files = Files::default();
+        for name in [
+            "../secret",
+            "hud/../../secret",
+            "/etc/passwd",
+            "hud//panel",
+            "hud\\..\\x",
+            ".hidden",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    files.path(name).unwrap_err().kind,
+                    ErrorKind::InvalidTemplateName(_)
+                ),
+                "{}",
+                name
+            );
+        }
+    }
+}
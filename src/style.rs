@@ -0,0 +1,255 @@
+//! A small subset of CSS used to style templates, rules are made of compound selectors
+//! built from a tag name, classes and ids, the declarations use the same names as attributes.
+use std::collections::HashMap;
+
+use crate::markup::{Error, ErrorKind, Result};
+
+/// Properties that are passed down from an element to its children unless they override them.
+pub const INHERITED: &[&str] = &["color", "font-size"];
+
+/// Shorthand properties that reset their longhands when declared with a higher priority.
+pub const SHORTHANDS: &[(&str, &[&str])] = &[
+    ("padding", &[
+        "padding-left",
+        "padding-right",
+        "padding-top",
+        "padding-bottom",
+    ]),
+    ("margin", &[
+        "margin-left",
+        "margin-right",
+        "margin-top",
+        "margin-bottom",
+    ]),
+    ("border-width", &[
+        "border-left-width",
+        "border-right-width",
+        "border-top-width",
+        "border-bottom-width",
+    ]),
+    ("gap", &["row-gap", "column-gap"]),
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct Stylesheet {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    selector: Selector,
+    declarations: Vec<(String, String)>,
+    order: usize,
+}
+
+impl Stylesheet {
+    pub fn parse(str: &str) -> Result<Self> {
+        let mut sheet = Self::default();
+        sheet.append(str)?;
+        Ok(sheet)
+    }
+
+    /// Appends the rules from the source, they take precedence over the existing rules
+    /// with the same specificity.
+    pub fn append(&mut self, str: &str) -> Result<()> {
+        let source = strip_comments(str);
+        let mut rest = source.trim();
+        let mut order = self.rules.last().map(|rule| rule.order + 1).unwrap_or(0);
+
+        while !rest.is_empty() {
+            let (selectors, body) = rest
+                .split_once('{')
+                .ok_or_else(|| invalid(format!("expected a block after '{}'", rest)))?;
+            let (body, tail) = body
+                .split_once('}')
+                .ok_or_else(|| invalid(format!("unclosed block after '{}'", selectors.trim())))?;
+
+            let declarations = parse_declarations(body)?;
+            for selector in selectors.split(',') {
+                self.rules.push(Rule {
+                    selector: Selector::parse(selector)?,
+                    declarations: declarations.clone(),
+                    order,
+                });
+            }
+            order += 1;
+            rest = tail.trim();
+        }
+        Ok(())
+    }
+
+    /// Resolves the properties of an element from the matching rules in cascade order,
+    /// starting with the values inherited from the parent.
+    pub fn resolve(
+        &self,
+        inherited: &HashMap<String, String>,
+        tag: &str,
+        id: Option<&str>,
+        classes: &[&str],
+    ) -> HashMap<String, String> {
+        let mut matching: Vec<&Rule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.selector.matches(tag, id, classes))
+            .collect();
+        matching.sort_by_key(|rule| (rule.selector.specificity(), rule.order));
+
+        let mut props = inherited.clone();
+        for rule in matching {
+            for (name, val) in &rule.declarations {
+                set_property(&mut props, name, val);
+            }
+        }
+        props
+    }
+}
+
+/// Sets a property, resetting its longhands if it's a shorthand.
+pub fn set_property(props: &mut HashMap<String, String>, name: &str, val: &str) {
+    if let Some((_, longhands)) = SHORTHANDS.iter().find(|(shorthand, _)| *shorthand == name) {
+        for longhand in *longhands {
+            props.remove(*longhand);
+        }
+    }
+    props.insert(name.to_owned(), val.to_owned());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector {
+    tag: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+}
+
+impl Selector {
+    fn parse(str: &str) -> Result<Self> {
+        let str = str.trim();
+        let unsupported = || invalid(format!("unsupported selector '{}'", str));
+        if str.is_empty() {
+            return Err(invalid("empty selector".to_owned()));
+        }
+
+        let mut selector = Selector {
+            tag: None,
+            ids: vec![],
+            classes: vec![],
+        };
+        let is_ident = |ch: char| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_';
+        let mut rest = str;
+        if let Some(tail) = rest.strip_prefix('*') {
+            rest = tail;
+        } else {
+            let end = rest.find(|ch| !is_ident(ch)).unwrap_or(rest.len());
+            if end > 0 {
+                selector.tag = Some(rest[..end].to_owned());
+                rest = &rest[end..];
+            }
+        }
+        while let Some(prefix) = rest.chars().next() {
+            let tail = &rest[prefix.len_utf8()..];
+            let end = tail.find(|ch| !is_ident(ch)).unwrap_or(tail.len());
+            if end == 0 {
+                return Err(unsupported());
+            }
+            match prefix {
+                '.' => selector.classes.push(tail[..end].to_owned()),
+                '#' => selector.ids.push(tail[..end].to_owned()),
+                _ => return Err(unsupported()),
+            }
+            rest = &tail[end..];
+        }
+        Ok(selector)
+    }
+
+    fn matches(&self, tag: &str, id: Option<&str>, classes: &[&str]) -> bool {
+        self.tag.as_deref().is_none_or(|name| name == tag)
+            && self.ids.iter().all(|name| id == Some(name.as_str()))
+            && self.classes.iter().all(|name| classes.contains(&name.as_str()))
+    }
+
+    fn specificity(&self) -> (usize, usize, usize) {
+        (self.ids.len(), self.classes.len(), self.tag.iter().count())
+    }
+}
+
+fn parse_declarations(str: &str) -> Result<Vec<(String, String)>> {
+    str.split(';')
+        .map(str::trim)
+        .filter(|decl| !decl.is_empty())
+        .map(|decl| {
+            let (name, val) = decl
+                .split_once(':')
+                .ok_or_else(|| invalid(format!("declaration '{}' has no value", decl)))?;
+            Ok((name.trim().to_owned(), val.trim().to_owned()))
+        })
+        .collect()
+}
+
+fn strip_comments(str: &str) -> String {
+    let mut res = String::with_capacity(str.len());
+    let mut rest = str;
+    while let Some(start) = rest.find("/*") {
+        res.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    res.push_str(rest);
+    res
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidStyle(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Stylesheet;
+
+    #[test]
+    fn it_cascades_by_specificity_and_order() -> anyhow::Result<()> {
+        let sheet = Stylesheet::parse(
+            "/* panels */
+             #main { width: 10pt }
+             box.panel { width: 20pt; height: 5pt }
+             .panel { width: 30pt; padding-left: 2 }
+             box { padding: 4; color: #ffffff }
+             .panel { padding-left: 3 }",
+        )?;
+        let props = sheet.resolve(&HashMap::new(), "box", None, &["panel"]);
+        assert_eq!(props["width"], "20pt");
+        assert_eq!(props["height"], "5pt");
+        assert_eq!(props["padding"], "4");
+        assert_eq!(props["padding-left"], "3");
+
+        let props = sheet.resolve(&HashMap::new(), "box", Some("main"), &["panel"]);
+        assert_eq!(props["width"], "10pt");
+
+        let props = sheet.resolve(&HashMap::new(), "text", None, &[]);
+        assert!(props.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn it_resets_longhands_with_shorthands() -> anyhow::Result<()> {
+        let sheet = Stylesheet::parse(".a { margin-top: 2 } .a.b { margin: 1 }")?;
+        let props = sheet.resolve(&HashMap::new(), "box", None, &["a", "b"]);
+        assert_eq!(props["margin"], "1");
+        assert!(!props.contains_key("margin-top"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_unsupported_syntax() {
+        assert!(Stylesheet::parse("box text { width: 1pt }").is_err());
+        assert!(Stylesheet::parse("box > text { width: 1pt }").is_err());
+        assert!(Stylesheet::parse("box { width 1pt }").is_err());
+        assert!(Stylesheet::parse("box { width: 1pt").is_err());
+        assert!(Stylesheet::parse("é { width: 1pt }").is_err());
+        assert!(Stylesheet::parse("boxé { width: 1pt }").is_err());
+        assert!(Stylesheet::parse(".aé { width: 1pt }").is_err());
+    }
+}
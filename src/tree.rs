@@ -0,0 +1,311 @@
+use flexlayout_rs::{Dimension, FlexAlign, FlexDirection, FlexProperty, FlexWrap};
+
+#[derive(Debug, Clone)]
+pub struct Elem {
+    pub kind: ElemKind,
+    pub id: Option<String>,
+    pub layout: Layout,
+    pub appearance: Appearance,
+    pub handlers: Vec<Handler>,
+    pub children: Vec<Elem>,
+}
+
+impl Elem {
+    pub fn new(kind: ElemKind) -> Self {
+        Self {
+            kind,
+            id: None,
+            layout: Layout::default(),
+            appearance: Appearance::default(),
+            handlers: vec![],
+            children: vec![],
+        }
+    }
+
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_id(mut self, id: Option<String>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_appearance(mut self, appearance: Appearance) -> Self {
+        self.appearance = appearance;
+        self
+    }
+
+    pub fn with_handlers(mut self, handlers: Vec<Handler>) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<Elem>) -> Self {
+        self.children = children;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ElemKind {
+    Box {
+        background_color: Option<Color>,
+        border_color: Option<Color>,
+        border_radius: Option<f32>,
+    },
+    Text {
+        text: String,
+        font_size: Option<i32>,
+        color: Option<Color>,
+    },
+    Image {
+        atlas: String,
+        part: Option<String>,
+        tint: Option<Color>,
+        nine_slice: bool,
+    },
+    /// An instance of a script class registered for a custom tag, it receives the attributes of the tag.
+    Custom {
+        class: String,
+        attributes: Vec<(String, String)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub position_type: PositionType,
+    pub flex_direction: FlexDirection,
+    pub flex_wrap: FlexWrap,
+    pub align_items: FlexAlign,
+    pub align_content: FlexAlign,
+    pub justify_content: FlexAlign,
+    pub align_self: FlexAlign,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub min_width: Dimension,
+    pub max_width: Dimension,
+    pub min_height: Dimension,
+    pub max_height: Dimension,
+    pub left: Dimension,
+    pub right: Dimension,
+    pub top: Dimension,
+    pub bottom: Dimension,
+    pub margin: Edges,
+    pub padding: Edges,
+    pub border: Edges,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub flex_basis: Dimension,
+    pub display: Display,
+    pub overflow: Overflow,
+    pub row_gap: f32,
+    pub column_gap: f32,
+}
+
+impl Layout {
+    pub fn properties(&self) -> Vec<FlexProperty> {
+        vec![
+            FlexProperty::Direction(self.flex_direction),
+            FlexProperty::Wrap(self.flex_wrap),
+            FlexProperty::AlignItems(self.align_items),
+            FlexProperty::AlignContent(self.align_content),
+            FlexProperty::JustifyContent(self.justify_content),
+            FlexProperty::AlignSelf(self.align_self),
+            FlexProperty::Height(self.height),
+            FlexProperty::Width(self.width),
+            FlexProperty::MinWidth(self.min_width),
+            FlexProperty::MaxWidth(self.max_width),
+            FlexProperty::MinHeight(self.min_height),
+            FlexProperty::MaxHeight(self.max_height),
+            FlexProperty::Left(self.left),
+            FlexProperty::Right(self.right),
+            FlexProperty::Top(self.top),
+            FlexProperty::Bottom(self.bottom),
+            FlexProperty::MarginLeft(self.margin.left),
+            FlexProperty::MarginRight(self.margin.right),
+            FlexProperty::MarginTop(self.margin.top),
+            FlexProperty::MarginBottom(self.margin.bottom),
+            FlexProperty::PaddingLeft(self.padding.left),
+            FlexProperty::PaddingRight(self.padding.right),
+            FlexProperty::PaddingTop(self.padding.top),
+            FlexProperty::PaddingBottom(self.padding.bottom),
+            FlexProperty::BorderLeft(self.border.left),
+            FlexProperty::BorderRight(self.border.right),
+            FlexProperty::BorderTop(self.border.top),
+            FlexProperty::BorderBottom(self.border.bottom),
+            FlexProperty::Grow(self.flex_grow),
+            FlexProperty::Shrink(self.flex_shrink),
+            FlexProperty::Basis(self.flex_basis),
+            FlexProperty::Fixed(self.position_type == PositionType::Absolute),
+        ]
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            position_type: PositionType::Relative,
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::NoWrap,
+            align_items: FlexAlign::Inherit,
+            align_content: FlexAlign::Inherit,
+            justify_content: FlexAlign::Inherit,
+            align_self: FlexAlign::Inherit,
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            min_width: Dimension::Auto,
+            max_width: Dimension::Auto,
+            min_height: Dimension::Auto,
+            max_height: Dimension::Auto,
+            left: Dimension::Auto,
+            right: Dimension::Auto,
+            top: Dimension::Auto,
+            bottom: Dimension::Auto,
+            margin: Edges::default(),
+            padding: Edges::default(),
+            border: Edges::default(),
+            flex_grow: 0f32,
+            flex_shrink: 1f32,
+            flex_basis: Dimension::Auto,
+            display: Display::Flex,
+            overflow: Overflow::Visible,
+            row_gap: 0f32,
+            column_gap: 0f32,
+        }
+    }
+}
+
+/// A method of the target object called when the event is triggered on the widget of an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handler {
+    pub event: Event,
+    pub method: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Click,
+    HoverEnter,
+    HoverLeave,
+}
+
+impl Event {
+    pub const ALL: [Event; 3] = [Event::Click, Event::HoverEnter, Event::HoverLeave];
+
+    pub fn attribute(self) -> &'static str {
+        match self {
+            Event::Click => "on-click",
+            Event::HoverEnter => "on-hover-enter",
+            Event::HoverLeave => "on-hover-leave",
+        }
+    }
+
+    /// Returns the name of the ink callback the event corresponds to.
+    pub fn callback(self) -> &'static str {
+        match self {
+            Event::Click => "OnRelease",
+            Event::HoverEnter => "OnHoverOver",
+            Event::HoverLeave => "OnHoverOut",
+        }
+    }
+}
+
+/// Properties applied to the widget of an element, they don't affect the layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Appearance {
+    pub opacity: f32,
+    pub visible: bool,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            opacity: 1f32,
+            visible: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Edges {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Edges {
+    pub fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum PositionType {
+    Relative = 0,
+    Absolute = 1,
+}
+
+impl Default for PositionType {
+    fn default() -> Self {
+        PositionType::Relative
+    }
+}
+
+/// Elements with `None` are left out of the layout and aren't rendered,
+/// they're kept in the element tree so that they can be shown again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum Display {
+    Flex = 0,
+    None = 1,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display::Flex
+    }
+}
+
+/// Children of elements with `Hidden` or `Scroll` are clipped to the padding box,
+/// children of scroll containers also keep their size instead of shrinking to fit it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum Overflow {
+    Visible = 0,
+    Hidden = 1,
+    Scroll = 2,
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Overflow::Visible
+    }
+}